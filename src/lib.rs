@@ -1,138 +1,2163 @@
+use chrono::{DateTime, Datelike, NaiveDate, Utc, Weekday};
+use futures::stream::{self, StreamExt, TryStreamExt};
 use reqwest::StatusCode;
-use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
 
-const API_BASE: &str = "https://www.recurse.com/api/v1";
+pub const API_BASE: &str = "https://www.recurse.com/api/v1";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_USER_AGENT: &str = concat!("tcurse/", env!("CARGO_PKG_VERSION"));
+const DEFAULT_CONCURRENCY: usize = 5;
+const PAGE_SIZE: usize = 50;
+const MAX_PAGES: usize = 100;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(5);
+const MAX_ERROR_BODY: usize = 500;
+const DEFAULT_RATE_LIMIT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RATE_LIMIT_DELAY: Duration = Duration::from_secs(60);
+const PATH_SEGMENT: &percent_encoding::AsciiSet = &percent_encoding::CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'\\')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
 
-#[derive(Debug, Deserialize)]
+pub mod time {
+    use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+    use chrono_tz::Tz;
+    use std::sync::OnceLock;
+
+    pub const DEFAULT_HUB_TZ: Tz = chrono_tz::America::New_York;
+
+    static HUB_TZ: OnceLock<Tz> = OnceLock::new();
+
+    /// Sets the timezone that "today" and hub times are judged in. Only the
+    /// first call has an effect; until then it is [`DEFAULT_HUB_TZ`].
+    pub fn set_hub_tz(tz: Tz) {
+        HUB_TZ.set(tz).ok();
+    }
+
+    pub fn hub_tz() -> Tz {
+        HUB_TZ.get().copied().unwrap_or(DEFAULT_HUB_TZ)
+    }
+
+    pub trait Clock: Send + Sync {
+        fn now(&self) -> DateTime<Utc>;
+    }
+
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct SystemClock;
+
+    impl Clock for SystemClock {
+        fn now(&self) -> DateTime<Utc> {
+            Utc::now()
+        }
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct FixedClock(pub DateTime<Utc>);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> DateTime<Utc> {
+            self.0
+        }
+    }
+
+    pub fn hub_now_with(clock: &dyn Clock) -> DateTime<Tz> {
+        clock.now().with_timezone(&hub_tz())
+    }
+
+    pub fn hub_today_with(clock: &dyn Clock) -> NaiveDate {
+        hub_now_with(clock).date_naive()
+    }
+
+    pub fn is_future_hub_date_with(date: NaiveDate, clock: &dyn Clock) -> bool {
+        date > hub_today_with(clock)
+    }
+
+    pub fn hub_now() -> DateTime<Tz> {
+        hub_now_with(&SystemClock)
+    }
+
+    pub fn hub_today() -> NaiveDate {
+        hub_today_with(&SystemClock)
+    }
+
+    pub fn is_future_hub_date(date: NaiveDate) -> bool {
+        is_future_hub_date_with(date, &SystemClock)
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum DateSpec {
+        Day(NaiveDate),
+        Month { start: NaiveDate, end: NaiveDate },
+    }
+
+    impl DateSpec {
+        pub fn range(&self) -> (NaiveDate, NaiveDate) {
+            match *self {
+                DateSpec::Day(date) => (date, date),
+                DateSpec::Month { start, end } => (start, end),
+            }
+        }
+
+        pub fn day(&self) -> Result<NaiveDate, String> {
+            match *self {
+                DateSpec::Day(date) => Ok(date),
+                DateSpec::Month { .. } => Err("This command needs a specific day (YYYY-MM-DD), not a whole month".to_string()),
+            }
+        }
+    }
+
+    pub fn parse_date_spec(s: &str) -> Result<DateSpec, String> {
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(DateSpec::Day(date));
+        }
+        let start = NaiveDate::parse_from_str(&format!("{}-01", s), "%Y-%m-%d")
+            .map_err(|_| "Invalid date format. Use YYYY-MM-DD (or YYYY-MM for a whole month)".to_string())?;
+        let end = start
+            .checked_add_months(chrono::Months::new(1))
+            .and_then(|next| next.pred_opt())
+            .ok_or_else(|| "Invalid date format. Use YYYY-MM-DD (or YYYY-MM for a whole month)".to_string())?;
+        Ok(DateSpec::Month { start, end })
+    }
+
+    pub fn parse_duration_spec(s: &str) -> Result<chrono::Duration, String> {
+        let invalid = || format!("Invalid duration '{}'. Use a number followed by d, w, or m, e.g. 14d, 2w, 3m", s);
+        let s = s.trim();
+        let split = s.len().checked_sub(1).filter(|i| s.is_char_boundary(*i)).ok_or_else(invalid)?;
+        let (count, unit) = s.split_at(split);
+        let count: i64 = count.parse().map_err(|_| invalid())?;
+        if count <= 0 {
+            return Err(invalid());
+        }
+        let days = match unit {
+            "d" => Some(count),
+            "w" => count.checked_mul(7),
+            "m" => count.checked_mul(30),
+            _ => return Err(invalid()),
+        };
+        days.and_then(chrono::TimeDelta::try_days)
+            .ok_or_else(|| format!("Duration '{}' is too long", s))
+    }
+
+    pub fn parse_relative_date(s: &str, now: NaiveDate) -> Result<NaiveDate, String> {
+        let s = s.trim();
+        match s.to_lowercase().as_str() {
+            "today" => return Ok(now),
+            "yesterday" => return now.pred_opt().ok_or_else(|| format!("Invalid date '{}'", s)),
+            _ => {}
+        }
+        if s.ends_with(['d', 'w', 'm']) && s[..s.len() - 1].chars().all(|c| c.is_ascii_digit()) {
+            return parse_duration_spec(s)
+                .ok()
+                .and_then(|ago| now.checked_sub_signed(ago))
+                .ok_or_else(|| format!("Invalid date '{}'. Relative dates need a count of at least 1 that stays within supported dates, e.g. 7d, 2w, or 3m", s));
+        }
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| {
+            format!("Invalid date '{}'. Use YYYY-MM-DD, today, yesterday, or a relative date like 7d, 2w, or 3m", s)
+        })
+    }
+
+    pub fn parse_timestamp(raw: &str) -> Option<DateTime<FixedOffset>> {
+        if let Ok(dt) = DateTime::parse_from_rfc3339(raw) {
+            return Some(dt);
+        }
+        if let Ok(dt) = DateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S %z") {
+            return Some(dt);
+        }
+        NaiveDateTime::parse_from_str(raw, "%Y-%m-%dT%H:%M:%S%.f")
+            .or_else(|_| NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S%.f"))
+            .ok()
+            .map(|naive| naive.and_utc().fixed_offset())
+    }
+}
+
+pub use time::{
+    hub_now, hub_today, hub_tz, is_future_hub_date, parse_date_spec, parse_duration_spec, parse_relative_date,
+    parse_timestamp, set_hub_tz, DateSpec, DEFAULT_HUB_TZ,
+};
+use time::{Clock, SystemClock};
+
+fn serialize_timestamp<S: Serializer>(value: &Option<&str>, serializer: S) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(raw) => match parse_timestamp(raw) {
+            Some(dt) => serializer.serialize_str(&dt.to_rfc3339()),
+            None => serializer.serialize_str(raw),
+        },
+        None => serializer.serialize_none(),
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Profile {
     pub id: i64,
     pub name: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub github: Option<String>,
+    #[serde(default)]
+    pub pronouns: Option<String>,
+    #[serde(default)]
+    pub zulip_id: Option<i64>,
+    #[serde(default)]
+    pub image_path: Option<String>,
+    #[serde(default, rename = "bio_rendered")]
+    pub bio: Option<String>,
+    #[serde(default)]
+    pub stints: Vec<Stint>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProfileSearch {
+    pub query: Option<String>,
+    pub batch_id: Option<i64>,
+    pub role: Option<String>,
+    pub scope: Option<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+impl ProfileSearch {
+    fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+        if let Some(query) = &self.query {
+            params.push(("query", query.clone()));
+        }
+        if let Some(batch_id) = self.batch_id {
+            params.push(("batch_id", batch_id.to_string()));
+        }
+        if let Some(role) = &self.role {
+            params.push(("role", role.clone()));
+        }
+        if let Some(scope) = &self.scope {
+            params.push(("scope", scope.clone()));
+        }
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        if let Some(offset) = self.offset {
+            params.push(("offset", offset.to_string()));
+        }
+        params
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Stint {
+    #[serde(default)]
+    pub batch: Option<Batch>,
+    #[serde(default)]
+    pub start_date: Option<String>,
+    #[serde(default)]
+    pub end_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Batch {
+    pub id: i64,
+    pub name: String,
+    #[serde(default)]
+    pub short_name: Option<String>,
+    #[serde(default)]
+    pub start_date: Option<String>,
+    #[serde(default)]
+    pub end_date: Option<String>,
+}
+
+impl Batch {
+    pub fn dates(&self) -> Option<(NaiveDate, NaiveDate)> {
+        let parse = |d: &Option<String>| NaiveDate::parse_from_str(d.as_deref()?, "%Y-%m-%d").ok();
+        Some((parse(&self.start_date)?, parse(&self.end_date)?))
+    }
+
+    pub fn in_session(&self, date: NaiveDate) -> bool {
+        self.dates().is_some_and(|(start, end)| start <= date && date <= end)
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize)]
 pub struct HubVisit {
     pub date: String,
     #[serde(default)]
     pub notes: Option<String>,
     pub person: VisitPerson,
+    #[serde(default)]
+    pub created_at: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+impl HubVisit {
+    /// Serialized visits include a `weekday` only when this succeeds; a
+    /// malformed date is written back unchanged, without one.
+    pub fn parsed_date(&self) -> Result<NaiveDate, chrono::ParseError> {
+        NaiveDate::parse_from_str(&self.date, "%Y-%m-%d")
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Serialize)]
+struct HubVisitJson<'a> {
+    date: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    weekday: Option<String>,
+    notes: Option<&'a str>,
+    person: &'a VisitPerson,
+    #[serde(serialize_with = "serialize_timestamp")]
+    created_at: Option<&'a str>,
+    #[serde(serialize_with = "serialize_timestamp")]
+    updated_at: Option<&'a str>,
+}
+
+impl Serialize for HubVisit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        HubVisitJson {
+            date: &self.date,
+            weekday: self
+                .parsed_date()
+                .ok()
+                .map(|d| d.format("%a").to_string()),
+            notes: self.notes.as_deref(),
+            person: &self.person,
+            created_at: self.created_at.as_deref(),
+            updated_at: self.updated_at.as_deref(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct VisitPerson {
     pub id: i64,
     pub name: String,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+pub fn parse_validation_errors(body: &str) -> Option<Vec<FieldError>> {
+    let value: serde_json::Value = serde_json::from_str(body).ok()?;
+    let mut errors = Vec::new();
+    match value.get("errors")? {
+        serde_json::Value::Object(fields) => {
+            for (field, messages) in fields {
+                match messages {
+                    serde_json::Value::Array(list) => {
+                        for message in list {
+                            errors.push(FieldError {
+                                field: field.clone(),
+                                message: message.as_str()?.to_string(),
+                            });
+                        }
+                    }
+                    serde_json::Value::String(message) => errors.push(FieldError {
+                        field: field.clone(),
+                        message: message.clone(),
+                    }),
+                    _ => return None,
+                }
+            }
+        }
+        serde_json::Value::Array(list) => {
+            for entry in list {
+                errors.push(FieldError {
+                    field: entry.get("field")?.as_str()?.to_string(),
+                    message: entry.get("message")?.as_str()?.to_string(),
+                });
+            }
+        }
+        _ => return None,
+    }
+    if errors.is_empty() {
+        return None;
+    }
+    Some(errors)
+}
+
+#[derive(Debug)]
+pub enum ApiError {
+    Request(reqwest::Error),
+    Timeout,
+    RateLimited { retry_after: Duration },
+    Http { status: StatusCode, body: String },
+    Parse { endpoint: &'static str, source: serde_json::Error, body: String },
+    NotFound,
+    InvalidDate(String),
+    Validation { errors: Vec<FieldError> },
+    CircuitOpen { failures: u32, cooldown: Duration },
+    TooManyPages { endpoint: &'static str, pages: usize },
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::Request(e) => write!(f, "Request failed: {}", e),
+            ApiError::Timeout => write!(f, "Request timed out"),
+            ApiError::RateLimited { retry_after } => write!(f, "Rate limited by the API, retry after {}s", retry_after.as_secs().max(1)),
+            ApiError::Http { status, body } if body.is_empty() => write!(f, "API error: {}", status),
+            ApiError::Http { status, body } => write!(f, "API error: {}: {}", status, body),
+            ApiError::Parse { endpoint, source, body } if body.is_empty() => {
+                write!(f, "Failed to parse response from {}: {}", endpoint, source)
+            }
+            ApiError::Parse { endpoint, source, body } => {
+                write!(f, "Failed to parse response from {}: {}: {}", endpoint, source, body)
+            }
+            ApiError::NotFound => write!(f, "API error: {}", StatusCode::NOT_FOUND),
+            ApiError::InvalidDate(message) => write!(f, "{}", message),
+            ApiError::Validation { errors } => {
+                let lines: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "Validation failed: {}", lines.join("; "))
+            }
+            ApiError::CircuitOpen { failures, cooldown } => write!(
+                f,
+                "Circuit open: skipping request after {} consecutive failures, retry after {}s cooldown",
+                failures,
+                cooldown.as_secs()
+            ),
+            ApiError::TooManyPages { endpoint, pages } => write!(
+                f,
+                "Stopped after {} full pages from {}; the results would be incomplete, so try a shorter range",
+                pages, endpoint
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ApiError::Request(e) => Some(e),
+            ApiError::Parse { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for ApiError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            return ApiError::Timeout;
+        }
+        ApiError::Request(e)
+    }
+}
+
+fn truncate_body(body: &str) -> String {
+    let body = body.trim();
+    if body.chars().count() <= MAX_ERROR_BODY {
+        return body.to_string();
+    }
+    let mut truncated: String = body.chars().take(MAX_ERROR_BODY).collect();
+    truncated.push('…');
+    truncated
+}
+
+pub async fn error_from_response(response: reqwest::Response) -> ApiError {
+    let status = response.status();
+    if status == StatusCode::NOT_FOUND {
+        return ApiError::NotFound;
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    if status == StatusCode::UNPROCESSABLE_ENTITY {
+        if let Some(errors) = parse_validation_errors(&body) {
+            return ApiError::Validation { errors };
+        }
+    }
+    ApiError::Http { status, body: truncate_body(&body) }
+}
+
+fn retry_after(response: &reqwest::Response) -> Duration {
+    let raw = match response.headers().get(reqwest::header::RETRY_AFTER).and_then(|v| v.to_str().ok()) {
+        Some(raw) => raw.trim(),
+        None => return DEFAULT_RATE_LIMIT_DELAY,
+    };
+    let delay = match raw.parse::<u64>() {
+        Ok(seconds) => Duration::from_secs(seconds),
+        Err(_) => match DateTime::parse_from_rfc2822(raw) {
+            Ok(at) => (at.with_timezone(&Utc) - SystemClock.now()).to_std().unwrap_or_default(),
+            Err(_) => DEFAULT_RATE_LIMIT_DELAY,
+        },
+    };
+    delay.min(MAX_RATE_LIMIT_DELAY)
+}
+
+async fn parse_json<T: DeserializeOwned>(response: reqwest::Response, endpoint: &'static str) -> Result<T, ApiError> {
+    let bytes = response.bytes().await?;
+    serde_json::from_slice(&bytes).map_err(|source| ApiError::Parse {
+        endpoint,
+        source,
+        body: truncate_body(&String::from_utf8_lossy(&bytes)),
+    })
+}
+
+pub fn extract_hashtags(notes: &str) -> Vec<String> {
+    notes
+        .split_whitespace()
+        .filter_map(|word| word.strip_prefix('#'))
+        .map(|tag| tag.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '-' && c != '_'))
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| tag.to_lowercase())
+        .collect()
+}
+
+pub trait VisitsExt {
+    fn unique_people(&self) -> Vec<&VisitPerson>;
+    fn count_by_person(&self) -> HashMap<i64, usize>;
+    fn dates_present(&self, person_id: i64) -> Vec<NaiveDate>;
+    fn with_notes(&self) -> Vec<&HubVisit>;
+}
+
+impl VisitsExt for [HubVisit] {
+    fn unique_people(&self) -> Vec<&VisitPerson> {
+        let mut people: Vec<&VisitPerson> = Vec::new();
+        for visit in self {
+            if !people.iter().any(|p| p.id == visit.person.id) {
+                people.push(&visit.person);
+            }
+        }
+        people
+    }
+
+    fn count_by_person(&self) -> HashMap<i64, usize> {
+        let mut counts = HashMap::new();
+        for visit in self {
+            *counts.entry(visit.person.id).or_default() += 1;
+        }
+        counts
+    }
+
+    fn dates_present(&self, person_id: i64) -> Vec<NaiveDate> {
+        let mut dates: Vec<NaiveDate> = self
+            .iter()
+            .filter(|v| v.person.id == person_id)
+            .filter_map(|v| v.parsed_date().ok())
+            .collect();
+        dates.sort();
+        dates.dedup();
+        dates
+    }
+
+    fn with_notes(&self) -> Vec<&HubVisit> {
+        self.iter()
+            .filter(|v| v.notes.as_deref().is_some_and(|n| !n.trim().is_empty()))
+            .collect()
+    }
+}
+
+pub fn compute_streaks(dates: &[NaiveDate], today: NaiveDate, skip_weekends: bool) -> (u32, u32) {
+    let is_weekend = |d: &NaiveDate| matches!(d.weekday(), Weekday::Sat | Weekday::Sun);
+    let next_day = |d: NaiveDate| {
+        let mut next = d.succ_opt().unwrap_or(d);
+        while skip_weekends && is_weekend(&next) {
+            next = next.succ_opt().unwrap_or(next);
+        }
+        next
+    };
+
+    let mut days: Vec<NaiveDate> = dates.iter().copied().filter(|d| !skip_weekends || !is_weekend(d)).collect();
+    days.sort();
+    days.dedup();
+
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for day in &days {
+        run = match previous {
+            Some(p) if next_day(p) == *day => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(*day);
+    }
+
+    let current = match previous {
+        Some(last) if last <= today && next_day(last) >= today => run,
+        _ => 0,
+    };
+    (current, longest)
+}
+
+pub fn presence(visits: &[HubVisit], person_ids: &[i64]) -> HashMap<i64, bool> {
+    person_ids
+        .iter()
+        .map(|id| (*id, visits.iter().any(|v| v.person.id == *id)))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    failures: u32,
+    opened_at: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct CircuitBreaker {
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    fn new(threshold: u32, cooldown: Duration) -> Self {
+        Self { threshold: threshold.max(1), cooldown, state: Mutex::new(BreakerState::default()) }
+    }
+
+    fn state(&self) -> CircuitState {
+        let state = self.state.lock().unwrap();
+        match state.opened_at {
+            Some(opened) if opened.elapsed() < self.cooldown => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+            None => CircuitState::Closed,
+        }
+    }
+
+    fn record(&self, success: bool) {
+        let mut state = self.state.lock().unwrap();
+        if success {
+            *state = BreakerState::default();
+            return;
+        }
+        state.failures += 1;
+        if state.failures >= self.threshold {
+            state.opened_at = Some(Instant::now());
+        }
+    }
+}
+
+fn parse_range(start: &str, end: &str) -> Result<(NaiveDate, NaiveDate), ApiError> {
+    let parse = |raw: &str| {
+        NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+            .map_err(|_| ApiError::InvalidDate(format!("Invalid date '{}'. Use YYYY-MM-DD", raw)))
+    };
+    let (first, last) = (parse(start)?, parse(end)?);
+    if first > last {
+        return Err(ApiError::InvalidDate(format!("Start date {} is after end date {}", start, end)));
+    }
+    Ok((first, last))
+}
+
+fn http_client(timeout: Duration, user_agent: &str) -> reqwest::Client {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .user_agent(user_agent)
+        .build()
+        .expect("Failed to build HTTP client")
+}
+
+#[derive(Clone)]
 pub struct ApiClient {
     client: reqwest::Client,
+    token: Arc<str>,
+    base_url: String,
+    request_timeout: Option<Duration>,
+    request_user_agent: Option<String>,
+    breaker: Option<Arc<CircuitBreaker>>,
+    retries: u32,
+    retry_base: Duration,
+    rate_limit_waits: u32,
+    concurrency: usize,
+    idempotency_keys: bool,
+    person_id: Option<i64>,
+    current_user: Arc<OnceLock<Profile>>,
+}
+
+pub struct ApiClientBuilder {
     token: String,
+    base_url: String,
+    timeout: Option<Duration>,
+    user_agent: Option<String>,
+    http_client: Option<reqwest::Client>,
+    retries: u32,
+    retry_base: Duration,
+    idempotency_keys: bool,
+    circuit_breaker: Option<(u32, Duration)>,
 }
 
-impl ApiClient {
-    pub fn new(token: String) -> Self {
+impl Default for ApiClientBuilder {
+    fn default() -> Self {
         Self {
-            client: reqwest::Client::new(),
-            token,
+            token: String::new(),
+            base_url: API_BASE.to_string(),
+            timeout: None,
+            user_agent: None,
+            http_client: None,
+            retries: 0,
+            retry_base: RETRY_BASE_DELAY,
+            idempotency_keys: false,
+            circuit_breaker: None,
         }
     }
+}
 
-    pub async fn get_current_user(&self) -> Result<Profile, String> {
-        let response = self.client
-            .get(format!("{}/profiles/me", API_BASE))
-            .bearer_auth(&self.token)
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+impl ApiClientBuilder {
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = token.into();
+        self
+    }
 
-        if !response.status().is_success() {
-            return Err(format!("API error: {}", response.status()));
-        }
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into();
+        self
+    }
 
-        response
-            .json::<Profile>()
-            .await
-            .map_err(|e| format!("Failed to parse response: {}", e))
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
     }
 
-    pub async fn get_visit(&self, person_id: i64, date: &str) -> Result<Option<HubVisit>, String> {
-        let response = self.client
-            .get(format!("{}/hub_visits/{}/{}", API_BASE, person_id, date))
-            .bearer_auth(&self.token)
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
 
-        if response.status() == StatusCode::NOT_FOUND {
-            return Ok(None);
+    /// Sends requests through `client` instead of one built by the builder.
+    /// A `timeout` or `user_agent` set on the builder is still applied to
+    /// every request, overriding the client's own.
+    pub fn http_client(mut self, client: reqwest::Client) -> Self {
+        self.http_client = Some(client);
+        self
+    }
+
+    /// Retries GETs up to `max` times on 5xx responses and connection
+    /// errors. PATCH and DELETE are only retried once `idempotency_keys` is
+    /// on: each call then carries one key across all of its attempts, so the
+    /// server can discard a duplicate of a write that did land.
+    pub fn retries(mut self, max: u32) -> Self {
+        self.retries = max;
+        self
+    }
+
+    /// The wait before the first retry; it doubles on each further attempt,
+    /// up to 5 seconds.
+    pub fn retry_base(mut self, delay: Duration) -> Self {
+        self.retry_base = delay;
+        self
+    }
+
+    pub fn idempotency_keys(mut self, enabled: bool) -> Self {
+        self.idempotency_keys = enabled;
+        self
+    }
+
+    pub fn circuit_breaker(mut self, threshold: u32, cooldown: Duration) -> Self {
+        self.circuit_breaker = Some((threshold, cooldown));
+        self
+    }
+
+    pub fn build(self) -> ApiClient {
+        let (client, request_timeout, request_user_agent) = match self.http_client {
+            Some(client) => (client, self.timeout, self.user_agent),
+            None => {
+                let user_agent = self.user_agent.as_deref().unwrap_or(DEFAULT_USER_AGENT);
+                (http_client(self.timeout.unwrap_or(DEFAULT_TIMEOUT), user_agent), None, None)
+            }
+        };
+        ApiClient {
+            client,
+            token: self.token.into(),
+            base_url: self.base_url.trim_end_matches('/').to_string(),
+            request_timeout,
+            request_user_agent,
+            breaker: self.circuit_breaker.map(|(threshold, cooldown)| Arc::new(CircuitBreaker::new(threshold, cooldown))),
+            retries: self.retries,
+            retry_base: self.retry_base,
+            rate_limit_waits: 3,
+            concurrency: DEFAULT_CONCURRENCY,
+            idempotency_keys: self.idempotency_keys,
+            person_id: None,
+            current_user: Arc::new(OnceLock::new()),
         }
+    }
+}
 
-        if !response.status().is_success() {
-            return Err(format!("API error: {}", response.status()));
+impl ApiClient {
+    pub fn builder() -> ApiClientBuilder {
+        ApiClientBuilder::default()
+    }
+
+    pub fn new(token: String) -> Self {
+        Self::builder().token(token).build()
+    }
+
+    pub fn with_base_url(token: String, base_url: String) -> Self {
+        Self::builder().token(token).base_url(base_url).build()
+    }
+
+    pub fn with_person_id(mut self, person_id: Option<i64>) -> Self {
+        self.person_id = person_id;
+        self
+    }
+
+    pub fn with_idempotency_keys(mut self, enabled: bool) -> Self {
+        self.idempotency_keys = enabled;
+        self
+    }
+
+    pub fn with_retries(mut self, max: u32) -> Self {
+        self.retries = max;
+        self
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    pub fn retries_enabled(&self) -> bool {
+        self.retries > 0
+    }
+
+    pub fn with_concurrency(mut self, max_in_flight: usize) -> Self {
+        self.concurrency = max_in_flight.max(1);
+        self
+    }
+
+    pub fn with_rate_limit_waits(mut self, max: u32) -> Self {
+        self.rate_limit_waits = max;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, threshold: u32, cooldown: Duration) -> Self {
+        self.breaker = Some(Arc::new(CircuitBreaker::new(threshold, cooldown)));
+        self
+    }
+
+    pub fn circuit_state(&self) -> Option<CircuitState> {
+        self.breaker.as_ref().map(|b| b.state())
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, ApiError> {
+        let mut waits = 0;
+        loop {
+            let attempt = match request.try_clone() {
+                Some(attempt) => attempt,
+                None => return self.send_once(request).await,
+            };
+            let response = self.send_once(attempt).await?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response);
+            }
+            let delay = retry_after(&response);
+            if waits >= self.rate_limit_waits {
+                return Err(ApiError::RateLimited { retry_after: delay });
+            }
+            tracing::debug!(delay_ms = delay.as_millis() as u64, "rate limited, waiting before retrying");
+            tokio::time::sleep(delay).await;
+            waits += 1;
         }
+    }
 
-        let visit = response
-            .json::<HubVisit>()
-            .await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
+    async fn send_once(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, ApiError> {
+        let breaker = match &self.breaker {
+            Some(b) => b,
+            None => return self.execute(request).await,
+        };
 
-        Ok(Some(visit))
+        if breaker.state() == CircuitState::Open {
+            return Err(ApiError::CircuitOpen {
+                failures: breaker.threshold,
+                cooldown: breaker.cooldown,
+            });
+        }
+
+        let result = self.execute(request).await;
+        breaker.record(matches!(&result, Ok(response) if !response.status().is_server_error()));
+        result
     }
 
-    pub async fn get_visits(&self, date: &str) -> Result<Vec<HubVisit>, String> {
-        let response = self.client
-            .get(format!("{}/hub_visits", API_BASE))
-            .query(&[("date", date)])
-            .bearer_auth(&self.token)
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+    async fn execute(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, ApiError> {
+        let request = request.build()?;
+        let method = request.method().clone();
+        let url = request.url().clone();
+        tracing::debug!(%method, %url, "sending request");
 
-        if !response.status().is_success() {
-            return Err(format!("API error: {}", response.status()));
+        let started = Instant::now();
+        let result = self.client.execute(request).await;
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        match &result {
+            Ok(response) => {
+                tracing::debug!(%method, %url, status = response.status().as_u16(), "received response");
+                tracing::trace!(%method, %url, elapsed_ms, "request finished");
+            }
+            Err(e) => tracing::debug!(%method, %url, elapsed_ms, error = %e, "request failed"),
         }
+        Ok(result?)
+    }
 
-        response
-            .json()
-            .await
-            .map_err(|e| format!("Failed to parse response: {}", e))
+    async fn send_with_retries(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, ApiError> {
+        let mut delay = self.retry_base;
+        for _ in 0..self.retries {
+            let attempt = match request.try_clone() {
+                Some(attempt) => attempt,
+                None => break,
+            };
+            match self.send(attempt).await {
+                Ok(response) if !response.status().is_server_error() => return Ok(response),
+                Ok(_) | Err(ApiError::Request(_) | ApiError::Timeout) => {}
+                Err(e) => return Err(e),
+            }
+            tracing::debug!(delay_ms = delay.as_millis() as u64, "retrying request");
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(RETRY_MAX_DELAY);
+        }
+        self.send(request).await
     }
 
-    pub async fn create_or_update_visit(&self, person_id: i64, date: &str, notes: Option<&str>) -> Result<HubVisit, String> {
-        let mut request = self.client
-            .patch(format!("{}/hub_visits/{}/{}", API_BASE, person_id, date))
-            .bearer_auth(&self.token);
+    async fn send_mutation(&self, mut request: reqwest::RequestBuilder) -> Result<reqwest::Response, ApiError> {
+        if !self.idempotency_keys {
+            return self.send(request).await;
+        }
+        request = request.header("Idempotency-Key", uuid::Uuid::new_v4().to_string());
+        self.send_with_retries(request).await
+    }
 
-        if let Some(n) = notes {
-            request = request.json(&serde_json::json!({ "notes": n }));
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let mut request = self
+            .client
+            .request(method, format!("{}/{}", self.base_url, path.trim_start_matches('/')))
+            .bearer_auth(&self.token);
+        if let Some(timeout) = self.request_timeout {
+            request = request.timeout(timeout);
+        }
+        if let Some(user_agent) = &self.request_user_agent {
+            request = request.header(reqwest::header::USER_AGENT, user_agent);
         }
+        request
+    }
+
+    /// Sends an authenticated GET for `path` (relative to the base URL) and
+    /// returns the response as-is, headers included. Only transport failures
+    /// become errors: callers must check `.status()` themselves before
+    /// reading the body.
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub async fn get_raw(&self, path: &str) -> Result<reqwest::Response, ApiError> {
+        self.send_with_retries(self.request(reqwest::Method::GET, path)).await
+    }
 
-        let response = request
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub async fn get_current_user(&self) -> Result<Profile, ApiError> {
+        let response = self.get_raw("profiles/me").await?;
 
         if !response.status().is_success() {
-            return Err(format!("API error: {}", response.status()));
+            return Err(error_from_response(response).await);
         }
 
-        response
-            .json()
-            .await
-            .map_err(|e| format!("Failed to parse response: {}", e))
+        let profile: Profile = parse_json(response, "GET /profiles/me").await?;
+        self.current_user.set(profile.clone()).ok();
+        Ok(profile)
+    }
+
+    pub fn fetched_profile(&self) -> Option<&Profile> {
+        self.current_user.get()
     }
 
-    pub async fn delete_visit(&self, person_id: i64, date: &str) -> Result<(), String> {
-        let response = self.client
-            .delete(format!("{}/hub_visits/{}/{}", API_BASE, person_id, date))
-            .bearer_auth(&self.token)
-            .send()
-            .await
-            .map_err(|e| format!("Request failed: {}", e))?;
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub async fn get_profile(&self, id_or_email: &str) -> Result<Profile, ApiError> {
+        let id_or_email = id_or_email.trim();
+        if matches!(id_or_email, "" | "." | "..") {
+            return Err(ApiError::NotFound);
+        }
+        let segment = percent_encoding::utf8_percent_encode(id_or_email, PATH_SEGMENT);
+        let response = self.get_raw(&format!("profiles/{}", segment)).await?;
 
         if !response.status().is_success() {
-            return Err(format!("API error: {}", response.status()));
+            return Err(error_from_response(response).await);
+        }
+
+        parse_json(response, "GET /profiles/:id").await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub async fn get_batches(&self) -> Result<Vec<Batch>, ApiError> {
+        let response = self.get_raw("batches").await?;
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        parse_json(response, "GET /batches").await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub async fn get_batch(&self, id: i64) -> Result<Batch, ApiError> {
+        let response = self.get_raw(&format!("batches/{}", id)).await?;
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        parse_json(response, "GET /batches/:id").await
+    }
+
+    pub async fn search_profiles(&self, query: &str) -> Result<Vec<Profile>, ApiError> {
+        self.search_profiles_with(&ProfileSearch { query: Some(query.to_string()), ..Default::default() }).await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub async fn search_profiles_with(&self, search: &ProfileSearch) -> Result<Vec<Profile>, ApiError> {
+        let request = self.request(reqwest::Method::GET, "profiles").query(&search.params());
+        let response = self.send_with_retries(request).await?;
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        parse_json(response, "GET /profiles").await
+    }
+
+    pub async fn current_user_id(&self) -> Result<i64, ApiError> {
+        match self.person_id {
+            Some(id) => Ok(id),
+            None => Ok(self.get_current_user().await?.id),
+        }
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub async fn get_visit(&self, person_id: i64, date: &str) -> Result<Option<HubVisit>, ApiError> {
+        let response = self.get_raw(&format!("hub_visits/{}/{}", person_id, date)).await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        let visit = parse_json(response, "GET /hub_visits/:person_id/:date").await?;
+
+        Ok(Some(visit))
+    }
+
+    async fn get_visit_pages(&self, query: &[(&str, String)]) -> Result<Vec<HubVisit>, ApiError> {
+        let mut visits = Vec::new();
+        for page in 0.. {
+            if page == MAX_PAGES {
+                return Err(ApiError::TooManyPages { endpoint: "GET /hub_visits", pages: MAX_PAGES });
+            }
+            let request = self
+                .request(reqwest::Method::GET, "hub_visits")
+                .query(query)
+                .query(&[("limit", PAGE_SIZE), ("offset", page * PAGE_SIZE)]);
+            let response = self.send_with_retries(request).await?;
+
+            if !response.status().is_success() {
+                return Err(error_from_response(response).await);
+            }
+
+            let batch: Vec<HubVisit> = parse_json(response, "GET /hub_visits").await?;
+            let last = batch.len() != PAGE_SIZE;
+            visits.extend(batch);
+            if last {
+                break;
+            }
+        }
+        Ok(visits)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub async fn get_visits(&self, date: &str) -> Result<Vec<HubVisit>, ApiError> {
+        self.get_visit_pages(&[("date", date.to_string())]).await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub async fn get_visits_range(&self, start: &str, end: &str) -> Result<Vec<HubVisit>, ApiError> {
+        let (first, last) = parse_range(start, end)?;
+        let mut visits = self
+            .get_visit_pages(&[
+                ("start_date", first.format("%Y-%m-%d").to_string()),
+                ("end_date", last.format("%Y-%m-%d").to_string()),
+            ])
+            .await?;
+        visits.sort_by(|a, b| a.date.cmp(&b.date));
+        Ok(visits)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub async fn get_person_visits(&self, person_id: i64, start: &str, end: &str) -> Result<Vec<HubVisit>, ApiError> {
+        let (first, last) = parse_range(start, end)?;
+
+        let days: Vec<Option<HubVisit>> = stream::iter(first.iter_days().take_while(|d| *d <= last))
+            .map(|day| async move { self.get_visit(person_id, &day.format("%Y-%m-%d").to_string()).await })
+            .buffered(self.concurrency)
+            .try_collect()
+            .await?;
+        Ok(days.into_iter().flatten().collect())
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub async fn attendance_calendar(&self, person_id: i64, start: NaiveDate, end: NaiveDate) -> Result<BTreeMap<NaiveDate, bool>, ApiError> {
+        let visits = self
+            .get_visit_pages(&[
+                ("person_id", person_id.to_string()),
+                ("start_date", start.format("%Y-%m-%d").to_string()),
+                ("end_date", end.format("%Y-%m-%d").to_string()),
+            ])
+            .await?;
+
+        let mut calendar: BTreeMap<NaiveDate, bool> = start.iter_days().take_while(|d| *d <= end).map(|d| (d, false)).collect();
+        for date in visits.dates_present(person_id) {
+            if let Some(present) = calendar.get_mut(&date) {
+                *present = true;
+            }
+        }
+
+        Ok(calendar)
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub async fn who_is_in(&self, person_ids: &[i64], date: &str) -> Result<HashMap<i64, bool>, ApiError> {
+        let visits = self.get_visits(date).await?;
+        Ok(presence(&visits, person_ids))
+    }
+
+    /// `None` leaves the visit's existing notes unchanged; `Some("")` clears them.
+    #[tracing::instrument(level = "debug", skip(self, notes), err(level = "debug"))]
+    pub async fn create_or_update_visit(&self, person_id: i64, date: &str, notes: Option<&str>) -> Result<HubVisit, ApiError> {
+        let mut request = self.request(reqwest::Method::PATCH, &format!("hub_visits/{}/{}", person_id, date));
+
+        if let Some(n) = notes {
+            request = request.json(&serde_json::json!({ "notes": n }));
+        }
+
+        let response = self.send_mutation(request).await?;
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
+        }
+
+        parse_json(response, "PATCH /hub_visits/:person_id/:date").await
+    }
+
+    #[tracing::instrument(level = "debug", skip(self), err(level = "debug"))]
+    pub async fn delete_visit(&self, person_id: i64, date: &str) -> Result<(), ApiError> {
+        let request = self.request(reqwest::Method::DELETE, &format!("hub_visits/{}/{}", person_id, date));
+        let response = self.send_mutation(request).await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+
+        if !response.status().is_success() {
+            return Err(error_from_response(response).await);
         }
 
         Ok(())
     }
 }
+
+/// The operations `ApiClient` offers, as a trait. Code that takes
+/// `&impl RecurseApi` (or `&dyn RecurseApi`) instead of `&ApiClient` can be
+/// handed a fake in tests; this is the recommended boundary for mocking.
+#[async_trait::async_trait]
+pub trait RecurseApi: Send + Sync {
+    async fn get_current_user(&self) -> Result<Profile, ApiError>;
+    async fn get_profile(&self, id_or_email: &str) -> Result<Profile, ApiError>;
+    async fn search_profiles(&self, query: &str) -> Result<Vec<Profile>, ApiError>;
+    async fn search_profiles_with(&self, search: &ProfileSearch) -> Result<Vec<Profile>, ApiError>;
+    async fn get_batches(&self) -> Result<Vec<Batch>, ApiError>;
+    async fn get_batch(&self, id: i64) -> Result<Batch, ApiError>;
+    async fn current_user_id(&self) -> Result<i64, ApiError>;
+    async fn get_visit(&self, person_id: i64, date: &str) -> Result<Option<HubVisit>, ApiError>;
+    async fn get_visits(&self, date: &str) -> Result<Vec<HubVisit>, ApiError>;
+    async fn get_visits_range(&self, start: &str, end: &str) -> Result<Vec<HubVisit>, ApiError>;
+    async fn get_person_visits(&self, person_id: i64, start: &str, end: &str) -> Result<Vec<HubVisit>, ApiError>;
+    async fn attendance_calendar(&self, person_id: i64, start: NaiveDate, end: NaiveDate) -> Result<BTreeMap<NaiveDate, bool>, ApiError>;
+    async fn who_is_in(&self, person_ids: &[i64], date: &str) -> Result<HashMap<i64, bool>, ApiError>;
+    async fn create_or_update_visit(&self, person_id: i64, date: &str, notes: Option<&str>) -> Result<HubVisit, ApiError>;
+    async fn delete_visit(&self, person_id: i64, date: &str) -> Result<(), ApiError>;
+}
+
+#[async_trait::async_trait]
+impl RecurseApi for ApiClient {
+    async fn get_current_user(&self) -> Result<Profile, ApiError> {
+        ApiClient::get_current_user(self).await
+    }
+
+    async fn get_profile(&self, id_or_email: &str) -> Result<Profile, ApiError> {
+        ApiClient::get_profile(self, id_or_email).await
+    }
+
+    async fn search_profiles(&self, query: &str) -> Result<Vec<Profile>, ApiError> {
+        ApiClient::search_profiles(self, query).await
+    }
+
+    async fn search_profiles_with(&self, search: &ProfileSearch) -> Result<Vec<Profile>, ApiError> {
+        ApiClient::search_profiles_with(self, search).await
+    }
+
+    async fn get_batches(&self) -> Result<Vec<Batch>, ApiError> {
+        ApiClient::get_batches(self).await
+    }
+
+    async fn get_batch(&self, id: i64) -> Result<Batch, ApiError> {
+        ApiClient::get_batch(self, id).await
+    }
+
+    async fn current_user_id(&self) -> Result<i64, ApiError> {
+        ApiClient::current_user_id(self).await
+    }
+
+    async fn get_visit(&self, person_id: i64, date: &str) -> Result<Option<HubVisit>, ApiError> {
+        ApiClient::get_visit(self, person_id, date).await
+    }
+
+    async fn get_visits(&self, date: &str) -> Result<Vec<HubVisit>, ApiError> {
+        ApiClient::get_visits(self, date).await
+    }
+
+    async fn get_visits_range(&self, start: &str, end: &str) -> Result<Vec<HubVisit>, ApiError> {
+        ApiClient::get_visits_range(self, start, end).await
+    }
+
+    async fn get_person_visits(&self, person_id: i64, start: &str, end: &str) -> Result<Vec<HubVisit>, ApiError> {
+        ApiClient::get_person_visits(self, person_id, start, end).await
+    }
+
+    async fn attendance_calendar(&self, person_id: i64, start: NaiveDate, end: NaiveDate) -> Result<BTreeMap<NaiveDate, bool>, ApiError> {
+        ApiClient::attendance_calendar(self, person_id, start, end).await
+    }
+
+    async fn who_is_in(&self, person_ids: &[i64], date: &str) -> Result<HashMap<i64, bool>, ApiError> {
+        ApiClient::who_is_in(self, person_ids, date).await
+    }
+
+    async fn create_or_update_visit(&self, person_id: i64, date: &str, notes: Option<&str>) -> Result<HubVisit, ApiError> {
+        ApiClient::create_or_update_visit(self, person_id, date, notes).await
+    }
+
+    async fn delete_visit(&self, person_id: i64, date: &str) -> Result<(), ApiError> {
+        ApiClient::delete_visit(self, person_id, date).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+    struct Recorded {
+        method: String,
+        path: String,
+        headers: Vec<(String, String)>,
+    }
+
+    impl Recorded {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+        }
+    }
+
+    struct Reply {
+        status: u16,
+        headers: Vec<(&'static str, String)>,
+        body: String,
+        delay: Duration,
+    }
+
+    impl Reply {
+        fn json(body: impl Into<String>) -> Self {
+            Reply::status(200, body)
+        }
+
+        fn status(status: u16, body: impl Into<String>) -> Self {
+            Reply { status, headers: Vec::new(), body: body.into(), delay: Duration::ZERO }
+        }
+
+        fn after(mut self, delay: Duration) -> Self {
+            self.delay = delay;
+            self
+        }
+
+        fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+            self.headers.push((name, value.into()));
+            self
+        }
+    }
+
+    struct MockServer {
+        base_url: String,
+        requests: Arc<Mutex<Vec<Recorded>>>,
+        connections: Arc<AtomicUsize>,
+    }
+
+    impl MockServer {
+        fn client(&self) -> ApiClient {
+            self.builder().build()
+        }
+
+        fn builder(&self) -> ApiClientBuilder {
+            ApiClient::builder().token("test-token").base_url(self.base_url.clone())
+        }
+
+        fn paths(&self) -> Vec<String> {
+            self.requests.lock().unwrap().iter().map(|r| format!("{} {}", r.method, r.path)).collect()
+        }
+    }
+
+    async fn mock_server(handler: impl Fn(&Recorded) -> Reply + Send + Sync + 'static) -> MockServer {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests: Arc<Mutex<Vec<Recorded>>> = Arc::default();
+        let handler = Arc::new(handler);
+
+        let connections: Arc<AtomicUsize> = Arc::default();
+        let (log, accepted) = (requests.clone(), connections.clone());
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let (handler, log) = (handler.clone(), log.clone());
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut reader = BufReader::new(read);
+                    loop {
+                        let mut line = String::new();
+                        if reader.read_line(&mut line).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        let mut parts = line.split_whitespace();
+                        let method = parts.next().unwrap_or_default().to_string();
+                        let path = parts.next().unwrap_or_default().to_string();
+
+                        let mut headers = Vec::new();
+                        loop {
+                            let mut line = String::new();
+                            reader.read_line(&mut line).await.unwrap();
+                            match line.trim_end().split_once(':') {
+                                Some((k, v)) => headers.push((k.trim().to_string(), v.trim().to_string())),
+                                None => break,
+                            }
+                        }
+                        let length = headers
+                            .iter()
+                            .find(|(k, _)| k.eq_ignore_ascii_case("content-length"))
+                            .map_or(0, |(_, v)| v.parse().unwrap());
+                        let mut body = vec![0; length];
+                        reader.read_exact(&mut body).await.unwrap();
+
+                        let request = Recorded { method, path, headers };
+                        let reply = handler(&request);
+                        log.lock().unwrap().push(request);
+
+                        tokio::time::sleep(reply.delay).await;
+                        let headers: String = reply.headers.iter().map(|(k, v)| format!("{}: {}\r\n", k, v)).collect();
+                        let response = format!(
+                            "HTTP/1.1 {} Mock\r\n{}Content-Length: {}\r\n\r\n{}",
+                            reply.status,
+                            headers,
+                            reply.body.len(),
+                            reply.body
+                        );
+                        if write.write_all(response.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+
+        MockServer { base_url, requests, connections }
+    }
+
+    fn roster(people: &[(i64, &str)]) -> String {
+        let visits: Vec<(&str, i64, &str)> = people.iter().map(|(id, name)| ("2024-01-15", *id, *name)).collect();
+        dated_roster(&visits)
+    }
+
+    fn dated_roster(visits: &[(&str, i64, &str)]) -> String {
+        let visits: Vec<serde_json::Value> = visits
+            .iter()
+            .map(|(date, id, name)| serde_json::json!({ "date": date, "person": { "id": id, "name": name } }))
+            .collect();
+        serde_json::Value::Array(visits).to_string()
+    }
+
+    fn visit(date: &str, id: i64, name: &str) -> HubVisit {
+        HubVisit {
+            date: date.to_string(),
+            notes: None,
+            person: VisitPerson { id, name: name.to_string() },
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn timestamps_serialize_as_rfc3339() {
+        let mut v = visit("2024-01-15", 1, "Ada Lovelace");
+        v.created_at = Some("2024-01-15 09:30:00 -0500".to_string());
+        v.updated_at = Some("2024-01-15T14:30:00.123".to_string());
+        let json = serde_json::to_value(&v).unwrap();
+        assert_eq!(json["created_at"], "2024-01-15T09:30:00-05:00");
+        assert_eq!(json["updated_at"], "2024-01-15T14:30:00.123+00:00");
+    }
+
+    #[test]
+    fn unparsable_timestamps_pass_through() {
+        let mut v = visit("2024-01-15", 1, "Ada Lovelace");
+        v.created_at = Some("yesterday-ish".to_string());
+        let json = serde_json::to_value(&v).unwrap();
+        assert_eq!(json["created_at"], "yesterday-ish");
+        assert!(json["updated_at"].is_null());
+    }
+
+    #[test]
+    fn timestamps_round_trip() {
+        let raw = r#"{"date":"2024-01-15","person":{"id":1,"name":"Ada"},"created_at":"2024-01-15T09:30:00-05:00","updated_at":"2024-01-15 10:00:00 -0500"}"#;
+        let first = serde_json::to_value(serde_json::from_str::<HubVisit>(raw).unwrap()).unwrap();
+        let second = serde_json::to_value(serde_json::from_value::<HubVisit>(first.clone()).unwrap()).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(second["created_at"], "2024-01-15T09:30:00-05:00");
+        assert_eq!(second["updated_at"], "2024-01-15T10:00:00-05:00");
+    }
+
+    #[test]
+    fn visits_serialize_with_their_weekday() {
+        let weekday = |date: &str| serde_json::to_value(visit(date, 1, "Ada")).unwrap()["weekday"].clone();
+        assert_eq!(weekday("2024-01-15"), "Mon");
+        assert_eq!(weekday("2024-02-29"), "Thu");
+        assert_eq!(weekday("2023-12-31"), "Sun");
+    }
+
+    #[tokio::test]
+    async fn who_is_in_reports_each_requested_id() {
+        let server = mock_server(|_| Reply::json(roster(&[(1, "Ada Lovelace"), (3, "Grace Hopper")]))).await;
+        let present = server.client().who_is_in(&[1, 2, 3], "2024-01-15").await.unwrap();
+        assert_eq!(present, HashMap::from([(1, true), (2, false), (3, true)]));
+        assert_eq!(server.paths(), ["GET /hub_visits?date=2024-01-15&limit=50&offset=0"]);
+    }
+
+    #[test]
+    fn presence_defaults_to_absent() {
+        let visits = vec![visit("2024-01-15", 1, "Ada Lovelace")];
+        assert_eq!(presence(&visits, &[1, 99]), HashMap::from([(1, true), (99, false)]));
+        assert!(presence(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn validation_errors_parse_field_maps() {
+        let errors = parse_validation_errors(r#"{"errors":{"notes":["is too long","is invalid"],"date":"can't be blank"}}"#).unwrap();
+        let rendered: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(rendered, ["date: can't be blank", "notes: is too long", "notes: is invalid"]);
+    }
+
+    #[test]
+    fn validation_errors_parse_lists() {
+        let errors = parse_validation_errors(r#"{"errors":[{"field":"notes","message":"is too long"}]}"#).unwrap();
+        assert_eq!(errors, [FieldError { field: "notes".to_string(), message: "is too long".to_string() }]);
+    }
+
+    #[test]
+    fn unexpected_validation_bodies_are_rejected() {
+        assert!(parse_validation_errors("not json").is_none());
+        assert!(parse_validation_errors(r#"{"message":"nope"}"#).is_none());
+        assert!(parse_validation_errors(r#"{"errors":{}}"#).is_none());
+        assert!(parse_validation_errors(r#"{"errors":{"notes":[1]}}"#).is_none());
+    }
+
+    #[tokio::test]
+    async fn a_422_becomes_a_validation_error() {
+        let server = mock_server(|_| Reply::status(422, r#"{"errors":{"notes":["is too long"]}}"#)).await;
+        let err = server.client().create_or_update_visit(1, "2024-01-15", Some("long")).await.unwrap_err();
+        assert!(matches!(&err, ApiError::Validation { errors } if errors.len() == 1));
+        assert_eq!(err.to_string(), "Validation failed: notes: is too long");
+    }
+
+    #[tokio::test]
+    async fn an_unrecognized_422_keeps_the_raw_body() {
+        let server = mock_server(|_| Reply::status(422, "date is bad")).await;
+        let err = server.client().create_or_update_visit(1, "2024-01-15", None).await.unwrap_err();
+        assert!(matches!(&err, ApiError::Http { status, body } if status.as_u16() == 422 && body == "date is bad"));
+    }
+
+    fn failing_then_ok(failures: usize) -> impl Fn(&Recorded) -> Reply {
+        failing_with_then_ok(500, failures)
+    }
+
+    fn failing_with_then_ok(status: u16, failures: usize) -> impl Fn(&Recorded) -> Reply {
+        let seen = AtomicUsize::new(0);
+        move |_| match seen.fetch_add(1, Ordering::SeqCst) < failures {
+            true => Reply::status(status, "down"),
+            false => Reply::json(r#"{"id":1,"name":"Ada"}"#),
+        }
+    }
+
+    #[tokio::test]
+    async fn circuit_opens_after_repeated_failures_and_closes_after_a_probe() {
+        let server = mock_server(failing_then_ok(2)).await;
+        let client = server.client().with_circuit_breaker(2, Duration::from_millis(50));
+        assert_eq!(client.circuit_state(), Some(CircuitState::Closed));
+
+        assert!(matches!(client.get_current_user().await, Err(ApiError::Http { .. })));
+        assert_eq!(client.circuit_state(), Some(CircuitState::Closed));
+        assert!(matches!(client.get_current_user().await, Err(ApiError::Http { .. })));
+        assert_eq!(client.circuit_state(), Some(CircuitState::Open));
+
+        assert!(matches!(client.get_current_user().await, Err(ApiError::CircuitOpen { failures: 2, .. })));
+        assert_eq!(server.paths().len(), 2);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(client.circuit_state(), Some(CircuitState::HalfOpen));
+        assert_eq!(client.get_current_user().await.unwrap().id, 1);
+        assert_eq!(client.circuit_state(), Some(CircuitState::Closed));
+    }
+
+    #[tokio::test]
+    async fn a_failed_probe_reopens_the_circuit() {
+        let server = mock_server(failing_then_ok(usize::MAX)).await;
+        let client = server.client().with_circuit_breaker(1, Duration::from_millis(50));
+        assert!(client.get_current_user().await.is_err());
+        assert_eq!(client.circuit_state(), Some(CircuitState::Open));
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(client.circuit_state(), Some(CircuitState::HalfOpen));
+        assert!(matches!(client.get_current_user().await, Err(ApiError::Http { .. })));
+        assert_eq!(client.circuit_state(), Some(CircuitState::Open));
+    }
+
+    #[tokio::test]
+    async fn the_builder_configures_the_circuit_breaker() {
+        let server = mock_server(failing_then_ok(usize::MAX)).await;
+        let client = server.builder().circuit_breaker(1, Duration::from_secs(60)).build();
+        assert_eq!(client.circuit_state(), Some(CircuitState::Closed));
+        assert!(matches!(client.get_current_user().await, Err(ApiError::Http { .. })));
+        assert!(matches!(client.get_current_user().await, Err(ApiError::CircuitOpen { failures: 1, .. })));
+        assert_eq!(server.paths().len(), 1);
+    }
+
+    #[test]
+    fn clients_without_a_breaker_report_no_state() {
+        assert_eq!(ApiClient::new("t".to_string()).circuit_state(), None);
+    }
+
+    #[tokio::test]
+    async fn attendance_calendar_marks_each_day_in_the_range() {
+        let server = mock_server(|_| {
+            Reply::json(dated_roster(&[
+                ("2024-01-15", 7, "Ada"),
+                ("2024-01-16", 8, "Grace"),
+                ("2024-01-17", 7, "Ada"),
+                ("2024-01-20", 7, "Ada"),
+            ]))
+        })
+        .await;
+        let date = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+        let calendar = server.client().attendance_calendar(7, date("2024-01-14"), date("2024-01-17")).await.unwrap();
+
+        let expected: BTreeMap<NaiveDate, bool> = [
+            (date("2024-01-14"), false),
+            (date("2024-01-15"), true),
+            (date("2024-01-16"), false),
+            (date("2024-01-17"), true),
+        ]
+        .into();
+        assert_eq!(calendar, expected);
+        assert_eq!(
+            server.paths(),
+            ["GET /hub_visits?person_id=7&start_date=2024-01-14&end_date=2024-01-17&limit=50&offset=0"]
+        );
+    }
+
+    #[tokio::test]
+    async fn idempotency_keys_are_reused_across_retries_only() {
+        let calls = AtomicUsize::new(0);
+        let server = mock_server(move |_| match calls.fetch_add(1, Ordering::SeqCst) {
+            0 => Reply::status(503, ""),
+            _ => Reply::json(serde_json::to_string(&visit("2024-01-15", 1, "Ada")).unwrap()),
+        })
+        .await;
+        let client = server.client().with_retries(1).with_idempotency_keys(true);
+        client.create_or_update_visit(1, "2024-01-15", None).await.unwrap();
+        client.create_or_update_visit(1, "2024-01-15", None).await.unwrap();
+
+        let keys: Vec<String> = server
+            .requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|r| r.header("Idempotency-Key").unwrap().to_string())
+            .collect();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys[0], keys[1]);
+        assert_ne!(keys[1], keys[2]);
+    }
+
+    #[tokio::test]
+    async fn a_retried_delete_reuses_its_idempotency_key() {
+        let server = mock_server(failing_with_then_ok(503, 1)).await;
+        server.builder().retries(1).idempotency_keys(true).build().delete_visit(1, "2024-01-15").await.unwrap();
+
+        let requests = server.requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|r| r.method == "DELETE"));
+        assert_eq!(requests[0].header("Idempotency-Key"), requests[1].header("Idempotency-Key"));
+    }
+
+    #[tokio::test]
+    async fn writes_are_not_retried_without_idempotency_keys() {
+        let server = mock_server(failing_with_then_ok(503, 1)).await;
+        let client = server.builder().retries(3).build();
+        assert!(matches!(client.create_or_update_visit(1, "2024-01-15", None).await, Err(ApiError::Http { .. })));
+        assert!(matches!(client.delete_visit(1, "2024-01-15").await, Ok(())));
+        assert_eq!(server.paths().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn idempotency_keys_are_off_by_default() {
+        let server = mock_server(|_| Reply::json(serde_json::to_string(&visit("2024-01-15", 1, "Ada")).unwrap())).await;
+        server.client().create_or_update_visit(1, "2024-01-15", None).await.unwrap();
+        assert!(server.requests.lock().unwrap()[0].header("Idempotency-Key").is_none());
+    }
+
+    fn clock_at(rfc3339: &str) -> time::FixedClock {
+        time::FixedClock(DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc))
+    }
+
+    fn day(date: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn hub_today_turns_over_at_midnight_in_new_york() {
+        assert_eq!(time::hub_today_with(&clock_at("2024-03-10T04:59:59Z")), day("2024-03-09"));
+        assert_eq!(time::hub_today_with(&clock_at("2024-03-10T05:00:00Z")), day("2024-03-10"));
+        assert_eq!(time::hub_today_with(&clock_at("2024-07-01T03:59:59Z")), day("2024-06-30"));
+        assert_eq!(time::hub_today_with(&clock_at("2024-07-01T04:00:00Z")), day("2024-07-01"));
+    }
+
+    #[test]
+    fn future_dates_are_judged_by_the_hub_date() {
+        let before_midnight = clock_at("2024-03-10T04:59:59Z");
+        assert!(!time::is_future_hub_date_with(day("2024-03-09"), &before_midnight));
+        assert!(time::is_future_hub_date_with(day("2024-03-10"), &before_midnight));
+
+        let after_midnight = clock_at("2024-03-10T05:00:00Z");
+        assert!(!time::is_future_hub_date_with(day("2024-03-10"), &after_midnight));
+        assert!(time::is_future_hub_date_with(day("2024-03-11"), &after_midnight));
+    }
+
+    #[tokio::test]
+    async fn a_configured_person_id_skips_the_profile_lookup() {
+        let server = mock_server(|_| Reply::json(r#"{"id":1,"name":"Ada"}"#)).await;
+        assert_eq!(server.client().with_person_id(Some(7)).current_user_id().await.unwrap(), 7);
+        assert!(server.paths().is_empty());
+
+        assert_eq!(server.client().current_user_id().await.unwrap(), 1);
+        assert_eq!(server.paths(), ["GET /profiles/me"]);
+    }
+
+    fn sample_visits() -> Vec<HubVisit> {
+        let noted = |date: &str, id: i64, name: &str, notes: &str| HubVisit { notes: Some(notes.to_string()), ..visit(date, id, name) };
+        vec![
+            noted("2024-01-16", 1, "Ada", "pairing"),
+            visit("2024-01-15", 2, "Grace"),
+            noted("2024-01-15", 1, "Ada", "  "),
+            visit("2024-01-16", 1, "Ada"),
+            noted("2024-01-17", 3, "Alan", "talks"),
+            visit("not a date", 1, "Ada"),
+        ]
+    }
+
+    #[test]
+    fn unique_people_keep_first_seen_order() {
+        let visits = sample_visits();
+        let ids: Vec<i64> = visits.unique_people().iter().map(|p| p.id).collect();
+        assert_eq!(ids, [1, 2, 3]);
+        assert!(Vec::<HubVisit>::new().unique_people().is_empty());
+    }
+
+    #[test]
+    fn count_by_person_counts_every_visit() {
+        assert_eq!(sample_visits().count_by_person(), HashMap::from([(1, 4), (2, 1), (3, 1)]));
+    }
+
+    #[test]
+    fn dates_present_are_sorted_unique_and_parsed() {
+        let visits = sample_visits();
+        assert_eq!(visits.dates_present(1), [day("2024-01-15"), day("2024-01-16")]);
+        assert_eq!(visits.dates_present(3), [day("2024-01-17")]);
+        assert!(visits.dates_present(99).is_empty());
+    }
+
+    #[test]
+    fn with_notes_skips_blank_notes() {
+        let visits = sample_visits();
+        let noted: Vec<&str> = visits.with_notes().iter().map(|v| v.notes.as_deref().unwrap()).collect();
+        assert_eq!(noted, ["pairing", "talks"]);
+    }
+
+    #[test]
+    fn hashtags_are_lowercased_and_stripped_of_trailing_punctuation() {
+        assert_eq!(extract_hashtags("#Rust pairing on #WASM, then #code_review."), ["rust", "wasm", "code_review"]);
+        assert_eq!(extract_hashtags("#RUST and #rust"), ["rust", "rust"]);
+        assert_eq!(extract_hashtags("#pair-programming!"), ["pair-programming"]);
+    }
+
+    #[test]
+    fn notes_without_tags_yield_none() {
+        assert!(extract_hashtags("").is_empty());
+        assert!(extract_hashtags("pairing on rust").is_empty());
+        assert!(extract_hashtags("issue# 12 and a lone # sign, #!").is_empty());
+    }
+
+    #[test]
+    fn months_expand_to_their_full_range() {
+        let month = |spec: &str| parse_date_spec(spec).unwrap().range();
+        assert_eq!(month("2024-01"), (day("2024-01-01"), day("2024-01-31")));
+        assert_eq!(month("2024-02"), (day("2024-02-01"), day("2024-02-29")));
+        assert_eq!(month("2023-02"), (day("2023-02-01"), day("2023-02-28")));
+        assert_eq!(month("1900-02"), (day("1900-02-01"), day("1900-02-28")));
+        assert_eq!(month("2000-02"), (day("2000-02-01"), day("2000-02-29")));
+        assert_eq!(month("2024-12"), (day("2024-12-01"), day("2024-12-31")));
+    }
+
+    #[test]
+    fn single_days_parse_and_refuse_month_only_use() {
+        assert_eq!(parse_date_spec("2024-02-29").unwrap(), DateSpec::Day(day("2024-02-29")));
+        assert_eq!(parse_date_spec("2024-02").unwrap().day().unwrap_err(), "This command needs a specific day (YYYY-MM-DD), not a whole month");
+        assert!(parse_date_spec("2023-02-29").is_err());
+        assert!(parse_date_spec("2024-13").is_err());
+        assert!(parse_date_spec("February").is_err());
+    }
+
+    #[test]
+    fn durations_parse_each_unit() {
+        assert_eq!(parse_duration_spec("14d"), Ok(chrono::Duration::days(14)));
+        assert_eq!(parse_duration_spec(" 2w "), Ok(chrono::Duration::days(14)));
+        assert_eq!(parse_duration_spec("3m"), Ok(chrono::Duration::days(90)));
+    }
+
+    #[test]
+    fn zero_negative_and_unknown_durations_are_rejected() {
+        for spec in ["0d", "-3d", "3y", "d", "", "3", "2 w", "3é"] {
+            assert!(parse_duration_spec(spec).unwrap_err().starts_with("Invalid duration"), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn overflowing_durations_are_rejected() {
+        for spec in ["999999999999999d", "99999999999999999w", "999999999999999999m", "99999999999999999999d"] {
+            assert!(parse_duration_spec(spec).is_err(), "{}", spec);
+        }
+    }
+
+    #[tokio::test]
+    async fn get_current_user_hits_profiles_me_under_the_base_url() {
+        let server = mock_server(|_| Reply::json(r#"{"id":1,"name":"Ada"}"#)).await;
+        for base_url in [format!("{}/api/v1", server.base_url), format!("{}/api/v1//", server.base_url)] {
+            let me = ApiClient::with_base_url("test-token".to_string(), base_url).get_current_user().await.unwrap();
+            assert_eq!((me.id, me.name.as_str()), (1, "Ada"));
+        }
+        assert_eq!(server.paths(), ["GET /api/v1/profiles/me", "GET /api/v1/profiles/me"]);
+        assert!(server.requests.lock().unwrap().iter().all(|r| r.header("Authorization") == Some("Bearer test-token")));
+    }
+
+    fn visit_by_person(request: &Recorded) -> Reply {
+        match request.path.split('/').nth(2) {
+            Some("1") => Reply::json(serde_json::to_string(&visit("2024-01-15", 1, "Ada")).unwrap()),
+            Some("2") => Reply::status(404, "not found"),
+            _ => Reply::status(500, "boom"),
+        }
+    }
+
+    fn assert_authorized(server: &MockServer) {
+        assert!(server.requests.lock().unwrap().iter().all(|r| r.header("Authorization") == Some("Bearer test-token")));
+    }
+
+    #[tokio::test]
+    async fn get_visit_returns_the_visit_none_on_404_and_errors_on_500() {
+        let server = mock_server(visit_by_person).await;
+        let client = server.client();
+
+        let found = client.get_visit(1, "2024-01-15").await.unwrap().unwrap();
+        assert_eq!((found.date.as_str(), found.person.id, found.person.name.as_str()), ("2024-01-15", 1, "Ada"));
+        assert!(client.get_visit(2, "2024-01-15").await.unwrap().is_none());
+        assert!(matches!(client.get_visit(3, "2024-01-15").await, Err(ApiError::Http { status, .. }) if status == 500));
+
+        assert_eq!(server.paths(), ["GET /hub_visits/1/2024-01-15", "GET /hub_visits/2/2024-01-15", "GET /hub_visits/3/2024-01-15"]);
+        assert_authorized(&server);
+    }
+
+    #[tokio::test]
+    async fn create_or_update_visit_returns_the_visit_and_surfaces_404_and_500() {
+        let server = mock_server(visit_by_person).await;
+        let client = server.client();
+
+        let saved = client.create_or_update_visit(1, "2024-01-15", Some("pairing")).await.unwrap();
+        assert_eq!((saved.date.as_str(), saved.person.id), ("2024-01-15", 1));
+        assert!(matches!(client.create_or_update_visit(2, "2024-01-15", None).await, Err(ApiError::NotFound)));
+        assert!(matches!(client.create_or_update_visit(3, "2024-01-15", None).await, Err(ApiError::Http { status, .. }) if status == 500));
+
+        assert_eq!(server.paths(), ["PATCH /hub_visits/1/2024-01-15", "PATCH /hub_visits/2/2024-01-15", "PATCH /hub_visits/3/2024-01-15"]);
+        assert_authorized(&server);
+    }
+
+    #[tokio::test]
+    async fn delete_visit_succeeds_on_200_and_404_and_errors_on_500() {
+        let server = mock_server(visit_by_person).await;
+        let client = server.client();
+
+        client.delete_visit(1, "2024-01-15").await.unwrap();
+        client.delete_visit(2, "2024-01-15").await.unwrap();
+        assert!(matches!(client.delete_visit(3, "2024-01-15").await, Err(ApiError::Http { status, .. }) if status == 500));
+
+        assert_eq!(server.paths(), ["DELETE /hub_visits/1/2024-01-15", "DELETE /hub_visits/2/2024-01-15", "DELETE /hub_visits/3/2024-01-15"]);
+        assert_authorized(&server);
+    }
+
+    fn offset(path: &str) -> usize {
+        let query = path.split_once('?').map_or("", |(_, q)| q);
+        query.split('&').find_map(|p| p.strip_prefix("offset=")).map_or(0, |o| o.parse().unwrap())
+    }
+
+    fn numbered_roster(ids: std::ops::Range<usize>) -> String {
+        let people: Vec<(i64, String)> = ids.map(|id| (id as i64, format!("P{}", id))).collect();
+        let people: Vec<(i64, &str)> = people.iter().map(|(id, name)| (*id, name.as_str())).collect();
+        roster(&people)
+    }
+
+    #[tokio::test]
+    async fn get_visits_follows_pages_in_order() {
+        let server = mock_server(|request| match offset(&request.path) {
+            0 => Reply::json(numbered_roster(0..PAGE_SIZE)),
+            _ => Reply::json(numbered_roster(PAGE_SIZE..PAGE_SIZE + 3)),
+        })
+        .await;
+        let visits = server.client().get_visits("2024-01-15").await.unwrap();
+        let ids: Vec<i64> = visits.iter().map(|v| v.person.id).collect();
+        assert_eq!(ids, (0..PAGE_SIZE as i64 + 3).collect::<Vec<_>>());
+        assert_eq!(
+            server.paths(),
+            ["GET /hub_visits?date=2024-01-15&limit=50&offset=0", "GET /hub_visits?date=2024-01-15&limit=50&offset=50"]
+        );
+    }
+
+    #[tokio::test]
+    async fn get_visits_stops_on_an_empty_page() {
+        let server = mock_server(|request| match offset(&request.path) {
+            0 => Reply::json(numbered_roster(0..PAGE_SIZE)),
+            _ => Reply::json("[]"),
+        })
+        .await;
+        assert_eq!(server.client().get_visits("2024-01-15").await.unwrap().len(), PAGE_SIZE);
+        assert_eq!(server.paths().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn get_visits_fails_instead_of_truncating_at_the_page_cap() {
+        let server = mock_server(|request| {
+            let start = offset(&request.path);
+            Reply::json(numbered_roster(start..start + PAGE_SIZE))
+        })
+        .await;
+        let err = server.client().get_visits("2024-01-15").await.unwrap_err();
+        assert!(matches!(err, ApiError::TooManyPages { pages: MAX_PAGES, .. }), "{}", err);
+        assert_eq!(server.paths().len(), MAX_PAGES);
+    }
+
+    #[tokio::test]
+    async fn two_503s_then_a_200_succeed_with_retries() {
+        let server = mock_server(failing_with_then_ok(503, 2)).await;
+        let client = server.builder().retries(2).build();
+        assert!(client.retries_enabled());
+        assert_eq!(client.retries(), 2);
+        assert_eq!(client.get_current_user().await.unwrap().name, "Ada");
+        assert_eq!(server.paths(), ["GET /profiles/me"; 3]);
+    }
+
+    #[tokio::test]
+    async fn requests_fail_once_retries_run_out() {
+        let server = mock_server(failing_with_then_ok(503, 2)).await;
+        let err = server.client().with_retries(1).get_current_user().await.unwrap_err();
+        assert!(matches!(err, ApiError::Http { status, .. } if status == StatusCode::SERVICE_UNAVAILABLE), "{}", err);
+        assert_eq!(server.paths().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn retries_are_off_by_default_and_skip_client_errors() {
+        let server = mock_server(failing_with_then_ok(503, 1)).await;
+        let client = server.client();
+        assert!(!client.retries_enabled());
+        assert!(client.get_current_user().await.is_err());
+        assert_eq!(server.paths().len(), 1);
+
+        let server = mock_server(failing_with_then_ok(400, 1)).await;
+        assert!(server.client().with_retries(3).get_current_user().await.is_err());
+        assert_eq!(server.paths().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_429_with_retry_after_is_waited_out() {
+        let calls = AtomicUsize::new(0);
+        let server = mock_server(move |_| match calls.fetch_add(1, Ordering::SeqCst) {
+            0 => Reply::status(429, "slow down").header("Retry-After", "0"),
+            _ => Reply::json(r#"{"id":1,"name":"Ada"}"#),
+        })
+        .await;
+        assert_eq!(server.client().get_current_user().await.unwrap().id, 1);
+        assert_eq!(server.paths().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rate_limiting_errors_once_the_waits_run_out() {
+        let server = mock_server(|_| Reply::status(429, "slow down").header("Retry-After", "0")).await;
+        let err = server.client().with_rate_limit_waits(2).get_current_user().await.unwrap_err();
+        assert!(matches!(err, ApiError::RateLimited { retry_after } if retry_after == Duration::ZERO), "{}", err);
+        assert_eq!(server.paths().len(), 3);
+    }
+
+    #[test]
+    fn visits_round_trip_through_json_unchanged() {
+        let raw = r#"{"date":"2024-02-29","notes":"pairing","person":{"id":1,"name":"Ada"},"created_at":null,"updated_at":null}"#;
+        let visit: HubVisit = serde_json::from_str(raw).unwrap();
+        assert_eq!(visit.parsed_date().unwrap(), day("2024-02-29"));
+
+        let first = serde_json::to_value(&visit).unwrap();
+        assert_eq!(first["date"], "2024-02-29");
+        assert_eq!(first["weekday"], "Thu");
+        let again: HubVisit = serde_json::from_value(first.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&again).unwrap(), first);
+    }
+
+    #[test]
+    fn malformed_dates_pass_through_without_a_weekday() {
+        let visit = visit("2024-02-30", 1, "Ada");
+        assert!(visit.parsed_date().is_err());
+
+        let json = serde_json::to_value(&visit).unwrap();
+        assert_eq!(json["date"], "2024-02-30");
+        assert!(json.get("weekday").is_none());
+        let again: HubVisit = serde_json::from_value(json).unwrap();
+        assert_eq!(again.date, "2024-02-30");
+    }
+
+    fn days(dates: &[&str]) -> Vec<NaiveDate> {
+        dates.iter().map(|d| day(d)).collect()
+    }
+
+    #[test]
+    fn streaks_of_empty_and_single_days() {
+        assert_eq!(compute_streaks(&[], day("2024-01-10"), false), (0, 0));
+        assert_eq!(compute_streaks(&days(&["2024-01-10"]), day("2024-01-10"), false), (1, 1));
+        assert_eq!(compute_streaks(&days(&["2024-01-09"]), day("2024-01-10"), false), (1, 1));
+        assert_eq!(compute_streaks(&days(&["2024-01-08"]), day("2024-01-10"), false), (0, 1));
+    }
+
+    #[test]
+    fn streaks_break_at_gaps() {
+        let dates = days(&["2024-01-06", "2024-01-01", "2024-01-02", "2024-01-03", "2024-01-05", "2024-01-02"]);
+        assert_eq!(compute_streaks(&dates, day("2024-01-06"), false), (2, 3));
+        assert_eq!(compute_streaks(&dates, day("2024-01-07"), false), (2, 3));
+        assert_eq!(compute_streaks(&dates, day("2024-01-08"), false), (0, 3));
+    }
+
+    #[test]
+    fn weekends_can_be_skipped() {
+        let dates = days(&["2024-01-11", "2024-01-12", "2024-01-13", "2024-01-15"]);
+        assert_eq!(compute_streaks(&dates, day("2024-01-15"), false), (1, 3));
+        assert_eq!(compute_streaks(&dates, day("2024-01-15"), true), (3, 3));
+        assert_eq!(compute_streaks(&days(&["2024-01-12"]), day("2024-01-15"), true), (1, 1));
+        assert_eq!(compute_streaks(&days(&["2024-01-13", "2024-01-14"]), day("2024-01-15"), true), (0, 0));
+    }
+
+    #[tokio::test]
+    async fn clones_share_one_connection_pool() {
+        let server = mock_server(|_| Reply::json(roster(&[(1, "Ada")]))).await;
+        let client = server.client();
+        let clones: Vec<ApiClient> = (0..4).map(|_| client.clone()).collect();
+
+        let rosters = futures::future::join_all(clones.iter().map(|c| c.get_visits("2024-01-15"))).await;
+        assert!(rosters.iter().all(|r| r.as_ref().is_ok_and(|v| v.len() == 1)));
+        let opened = server.connections.load(Ordering::SeqCst);
+        assert!((1..=4).contains(&opened), "{}", opened);
+
+        for clone in &clones {
+            clone.get_visits("2024-01-16").await.unwrap();
+        }
+        client.get_visits("2024-01-17").await.unwrap();
+        assert_eq!(server.connections.load(Ordering::SeqCst), opened);
+        assert_eq!(server.paths().len(), 9);
+    }
+
+    #[tokio::test]
+    async fn separately_built_clients_open_their_own_connections() {
+        let server = mock_server(|_| Reply::json("[]")).await;
+        server.client().get_visits("2024-01-15").await.unwrap();
+        server.client().get_visits("2024-01-15").await.unwrap();
+        assert_eq!(server.connections.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn requests_identify_tcurse_in_the_user_agent() {
+        let server = mock_server(|_| Reply::json("[]")).await;
+        server.client().get_visits("2024-01-15").await.unwrap();
+        server.builder().user_agent("wiki-bot/2").build().get_visits("2024-01-15").await.unwrap();
+
+        let agents: Vec<String> = server.requests.lock().unwrap().iter().map(|r| r.header("User-Agent").unwrap().to_string()).collect();
+        assert_eq!(agents, [format!("tcurse/{}", env!("CARGO_PKG_VERSION")), "wiki-bot/2".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn builder_settings_apply_in_any_order_and_keep_a_custom_client() {
+        let server = mock_server(|_| Reply::json("[]").after(Duration::from_millis(200))).await;
+        let custom = || reqwest::Client::builder().user_agent("proxy-bot/1").build().unwrap();
+        server.builder().http_client(custom()).build().get_visits("2024-01-15").await.unwrap();
+
+        let overridden = server.builder().http_client(custom()).timeout(Duration::from_millis(50)).user_agent("custom/1").build();
+        assert!(matches!(overridden.get_visits("2024-01-15").await, Err(ApiError::Timeout)));
+
+        let timed_out = ApiClient::builder().timeout(Duration::from_millis(50)).user_agent("late/1").token("test-token").base_url(server.base_url.clone()).build();
+        assert!(matches!(timed_out.get_visits("2024-01-15").await, Err(ApiError::Timeout)));
+
+        let agents: Vec<String> = server.requests.lock().unwrap().iter().map(|r| r.header("User-Agent").unwrap().to_string()).collect();
+        assert_eq!(agents, ["proxy-bot/1", "custom/1", "late/1"]);
+    }
+
+    fn slower_for_earlier_days(request: &Recorded) -> Reply {
+        let date = request.path.rsplit('/').next().unwrap().to_string();
+        let delay = match date.as_str() {
+            "2024-01-15" => 150,
+            "2024-01-16" => 75,
+            _ => 0,
+        };
+        Reply::json(serde_json::to_string(&visit(&date, 1, "Ada")).unwrap()).after(Duration::from_millis(delay))
+    }
+
+    #[tokio::test]
+    async fn range_results_stay_in_date_order_when_later_days_finish_first() {
+        let server = mock_server(slower_for_earlier_days).await;
+        let client = server.client().with_concurrency(3);
+
+        let dates = |visits: Vec<HubVisit>| visits.into_iter().map(|v| v.date).collect::<Vec<_>>();
+        assert_eq!(dates(client.get_person_visits(1, "2024-01-15", "2024-01-17").await.unwrap()), ["2024-01-15", "2024-01-16", "2024-01-17"]);
+    }
+
+    #[tokio::test]
+    async fn get_visits_range_queries_the_range_once_and_sorts_by_date() {
+        let server = mock_server(|_| {
+            Reply::json(dated_roster(&[
+                ("2024-01-17", 1, "Ada"),
+                ("2024-01-15", 2, "Grace"),
+                ("2024-01-17", 3, "Alan"),
+                ("2024-01-15", 1, "Ada"),
+            ]))
+        })
+        .await;
+        let visits = server.client().get_visits_range("2024-01-15", "2024-01-17").await.unwrap();
+        let order: Vec<(&str, i64)> = visits.iter().map(|v| (v.date.as_str(), v.person.id)).collect();
+        assert_eq!(order, [("2024-01-15", 2), ("2024-01-15", 1), ("2024-01-17", 1), ("2024-01-17", 3)]);
+        assert_eq!(server.paths(), ["GET /hub_visits?start_date=2024-01-15&end_date=2024-01-17&limit=50&offset=0"]);
+
+        assert!(matches!(server.client().get_visits_range("2024-01-17", "2024-01-15").await, Err(ApiError::InvalidDate(_))));
+        assert_eq!(server.paths().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn malformed_json_names_the_endpoint() {
+        let server = mock_server(|request| match request.path.as_str() {
+            "/profiles/me" => Reply::json(r#"{"name":"Ada"}"#),
+            _ => Reply::json(r#"[{"date":"2024-01-15"}]"#),
+        })
+        .await;
+        let client = server.client();
+
+        let err = client.get_visits("2024-01-15").await.unwrap_err();
+        assert!(matches!(err, ApiError::Parse { endpoint: "GET /hub_visits", .. }), "{:?}", err);
+        let message = err.to_string();
+        assert!(message.starts_with("Failed to parse response from GET /hub_visits: missing field `person`"), "{}", message);
+        assert!(message.ends_with(r#"[{"date":"2024-01-15"}]"#), "{}", message);
+
+        let err = client.get_current_user().await.unwrap_err();
+        assert!(err.to_string().starts_with("Failed to parse response from GET /profiles/me"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn parse_errors_truncate_long_bodies() {
+        let server = mock_server(|_| Reply::json(format!("[{}", "x".repeat(2 * MAX_ERROR_BODY)))).await;
+        let err = server.client().get_visits("2024-01-15").await.unwrap_err();
+        match err {
+            ApiError::Parse { body, .. } => {
+                assert_eq!(body.chars().count(), MAX_ERROR_BODY + 1);
+                assert!(body.ends_with('…'));
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn relative_dates_count_back_from_now() {
+        let now = day("2024-03-01");
+        assert_eq!(parse_relative_date("today", now), Ok(now));
+        assert_eq!(parse_relative_date(" Yesterday ", now), Ok(day("2024-02-29")));
+        assert_eq!(parse_relative_date("7d", now), Ok(day("2024-02-23")));
+        assert_eq!(parse_relative_date("2w", now), Ok(day("2024-02-16")));
+        assert_eq!(parse_relative_date("1m", now), Ok(day("2024-01-31")));
+        assert_eq!(parse_relative_date("2023-12-25", now), Ok(day("2023-12-25")));
+    }
+
+    #[test]
+    fn garbage_relative_dates_are_rejected() {
+        for input in ["", "tomorrow", "7 days", "d", "7y", "-7d", "2024-02-30", "01/03/2024"] {
+            assert!(parse_relative_date(input, day("2024-03-01")).unwrap_err().starts_with("Invalid date"), "{}", input);
+        }
+        assert!(parse_relative_date("0d", day("2024-03-01")).unwrap_err().starts_with("Invalid date '0d'"));
+    }
+
+    #[test]
+    fn overflowing_relative_dates_are_invalid_dates() {
+        for input in ["999999999999d", "99999999999999999w", "999999999999999999m", "99999999999999999999d"] {
+            assert!(parse_relative_date(input, day("2024-03-01")).unwrap_err().starts_with(&format!("Invalid date '{}'", input)), "{}", input);
+        }
+    }
+
+    #[tokio::test]
+    async fn profile_lookups_escape_the_path_segment() {
+        let server = mock_server(|_| Reply::json(r#"{"id":1,"name":"Ada"}"#)).await;
+        let client = server.client();
+        for id_or_email in [" 42 ", "ada@example.com", "a b/../me?x=1#top", "%2F", "café"] {
+            client.get_profile(id_or_email).await.unwrap();
+        }
+        for id_or_email in ["", " ", ".", ".."] {
+            assert!(matches!(client.get_profile(id_or_email).await, Err(ApiError::NotFound)), "{:?}", id_or_email);
+        }
+        assert_eq!(
+            server.paths(),
+            [
+                "GET /profiles/42",
+                "GET /profiles/ada@example.com",
+                "GET /profiles/a%20b%2F..%2Fme%3Fx=1%23top",
+                "GET /profiles/%252F",
+                "GET /profiles/caf%C3%A9",
+            ]
+        );
+    }
+}
@@ -1,121 +1,4665 @@
-use chrono::{Local, NaiveDate};
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, Timelike, Weekday};
+use chrono_tz::Tz;
+use clap::{Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
-use tcurse::ApiClient;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::fs::File;
+use std::io::Write;
+use std::sync::{Mutex, Once, OnceLock};
+use std::fmt;
+use tcurse::{ApiClient, ApiError, Batch, HubVisit, Profile, VisitPerson, VisitsExt};
 
 #[derive(Parser)]
 #[command(name = "tcurse")]
 #[command(about = "CLI tool for interacting with the Recurse Center API")]
+#[command(after_help = "Aliases: add <name> = \"<args>\" under [alias] in config.toml, or set TCURSE_ALIAS_<NAME>=\"<args>\" to override one, to run them as `tcurse <name>`
+
+Retries apply to each request on its own: commands that make many requests (history, import, backfill, or a long range fetched page by page) can retry every one of them, so N retries means up to N+1 attempts per request rather than per command
+
+Dates like today and 7d, and hub times, are in TCURSE_TIMEZONE (default America/New_York)
+
+Long-running commands (daemon, tui, checked-in --watch) stop calling the API for a cooldown after repeated failures; set TCURSE_CIRCUIT_BREAKER=FAILURES,SECONDS to tune it (default 5,30)
+
+Set TCURSE_IDEMPOTENCY_KEYS=true to send an Idempotency-Key header with each check-in and removal; they are only retried when this is on, and reuse the key across attempts
+
+Exit codes: 0 success, 1 error, 2 missing or rejected token (401/403) or invalid usage, 3 not found, 4 network error, timeout, or rate limit")]
 struct Cli {
+    /// Suppress informational notes and success messages; errors still go to stderr
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Log API requests and response statuses to stderr (-vv adds timing)
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Extra .env file to load (takes precedence over ./.env and the config directory's .env; never overrides already-set variables)
+    #[arg(long, global = true)]
+    env_file: Option<PathBuf>,
+    /// How to display names in listings (also set by TCURSE_NAME_FORMAT)
+    #[arg(long, global = true, value_enum)]
+    name_format: Option<NameFormat>,
+    /// Describe what the command would do without running it or touching the network
+    #[arg(long, global = true)]
+    explain: bool,
+    /// Retry requests that fail with a 5xx, timeout, or connection error up to N times, at most 10 (also set by TCURSE_RETRIES)
+    #[arg(long, global = true, value_name = "N", value_parser = clap::value_parser!(u32).range(..=MAX_RETRIES as i64))]
+    retries: Option<u32>,
+    /// Milliseconds to wait before the first retry, doubling on each further one (1-10000, default 200)
+    #[arg(long, global = true, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..=10_000))]
+    retry_base_ms: Option<u64>,
+    /// Fail on the first error instead of retrying, overriding --retries and TCURSE_RETRIES
+    #[arg(long, global = true, conflicts_with_all = ["retries", "retry_base_ms"])]
+    no_retry: bool,
+    /// When to color output: auto (only on a terminal with NO_COLOR unset), always, or never (also set by TCURSE_COLOR)
+    #[arg(long, global = true, value_enum)]
+    color: Option<ColorChoice>,
+    /// Print results as text (the default), json, or csv (csv is only supported by range and checked-in)
+    #[arg(long, global = true, value_enum)]
+    output: Option<OutputFormat>,
+    /// Write the command's output to this file instead of stdout, replacing it only if the command succeeds ("-" for stdout)
+    #[arg(long, global = true, value_name = "PATH")]
+    output_file: Option<PathBuf>,
+    /// Look up your profile even if TCURSE_PERSON_ID is set or a cached profile exists (use after changing tokens)
+    #[arg(long, visible_alias = "refresh", global = true)]
+    refresh_profile: bool,
+    /// Print what checkin, import, backfill, or the tui check-in toggle would change without sending any PATCH or DELETE requests
+    #[arg(long, global = true)]
+    dry_run: bool,
     #[command(subcommand)]
     command: Commands,
 }
 
+#[derive(Debug)]
+enum CliError {
+    Api(ApiError),
+    Auth(String),
+    Message(String),
+}
+
+impl CliError {
+    fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Auth(_) => 2,
+            CliError::Api(ApiError::Http { status, .. }) if matches!(status.as_u16(), 401 | 403) => 2,
+            CliError::Api(ApiError::NotFound) => 3,
+            CliError::Api(ApiError::Request(_) | ApiError::Timeout | ApiError::RateLimited { .. } | ApiError::CircuitOpen { .. }) => 4,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Api(e) => write!(f, "{}", e),
+            CliError::Auth(m) | CliError::Message(m) => write!(f, "{}", m),
+        }
+    }
+}
+
+impl From<ApiError> for CliError {
+    fn from(e: ApiError) -> Self {
+        CliError::Api(e)
+    }
+}
+
+impl From<String> for CliError {
+    fn from(m: String) -> Self {
+        CliError::Message(m)
+    }
+}
+
+const FIRST_HUB_YEAR: i32 = 2011;
+const MAX_RETRIES: u32 = 10;
+
+static OUTPUT_FILE: OnceLock<Mutex<OutputFile>> = OnceLock::new();
+static COLOR: OnceLock<bool> = OnceLock::new();
+
+const BOLD: &str = "1";
+const DIM: &str = "2";
+
+fn init_color(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => OUTPUT_FILE.get().is_none() && std::io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+    };
+    COLOR.set(enabled).ok();
+}
+
+fn use_color() -> bool {
+    COLOR.get().copied().unwrap_or(false)
+}
+
+fn paint(text: &str, style: &str) -> String {
+    if use_color() {
+        format!("\x1b[{}m{}\x1b[0m", style, text)
+    } else {
+        text.to_string()
+    }
+}
+
+struct OutputFile {
+    file: File,
+    temp: PathBuf,
+    path: PathBuf,
+}
+
+impl OutputFile {
+    fn create(path: &Path) -> Result<Self, String> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| format!("Failed to open {}: not a file path", path.display()))?;
+        let temp = path.with_file_name(format!(".{}.{}.tmp", name.to_string_lossy(), std::process::id()));
+        let file = File::create(&temp).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        Ok(OutputFile { file, temp, path: path.to_path_buf() })
+    }
+
+    fn finish(&mut self, success: bool) -> Result<(), String> {
+        if !success {
+            let _ = std::fs::remove_file(&self.temp);
+            return Ok(());
+        }
+        self.file
+            .flush()
+            .and_then(|_| std::fs::rename(&self.temp, &self.path))
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&self.temp);
+                format!("Failed to write {}: {}", self.path.display(), e)
+            })
+    }
+}
+
+fn open_output(path: &Path) -> Result<(), String> {
+    if path == Path::new("-") {
+        return Ok(());
+    }
+    OUTPUT_FILE.set(Mutex::new(OutputFile::create(path)?)).ok();
+    Ok(())
+}
+
+fn finish_output(success: bool) -> Result<(), String> {
+    match OUTPUT_FILE.get() {
+        Some(output) => output.lock().unwrap().finish(success),
+        None => Ok(()),
+    }
+}
+
+fn write_output(text: &str) {
+    let result = match OUTPUT_FILE.get() {
+        Some(output) => output.lock().unwrap().file.write_all(text.as_bytes()),
+        None => std::io::stdout().write_all(text.as_bytes()),
+    };
+    if let Err(e) = result {
+        eprintln!("Error: failed to write output: {}", e);
+        let _ = finish_output(false);
+        std::process::exit(1);
+    }
+}
+
+macro_rules! out {
+    ($($arg:tt)*) => {
+        write_output(&format!($($arg)*))
+    };
+}
+
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        write_output(&format!("{}\n", format_args!($($arg)*)))
+    };
+}
+
 #[derive(Subcommand)]
 enum Commands {
-    /// Check in to the hub (creates or updates your visit for today)
-    Checkin {
-        /// Optional notes to add to your check-in
-        #[arg(short, long)]
-        notes: Option<String>,
-        /// Remove your check-in instead of creating one
-        #[arg(short, long)]
-        remove: bool,
-    },
+    /// Check in to the hub (creates or updates your visit for today, or --date)
+    Checkin(CheckinArgs),
     /// View who is checked in today
-    CheckedIn {
-        /// Date to check (defaults to today, format: YYYY-MM-DD)
+    CheckedIn(CheckedInArgs),
+    /// Show which Recurse account your RC_TOKEN belongs to
+    Whoami {
+        /// Deprecated: use --output json
+        #[arg(long, hide = true)]
+        json: bool,
+    },
+    /// Search Recurse profiles by name and print their person ids
+    Find {
+        /// Name or other text to search for
+        query: String,
+        /// Deprecated: use --output json
+        #[arg(long, hide = true)]
+        json: bool,
+    },
+    /// Show someone's profile: pronouns, contact details, and batches
+    Who {
+        /// Person id, email address, or a name to search for
+        person: String,
+        /// Deprecated: use --output json
+        #[arg(long, hide = true)]
+        json: bool,
+    },
+    /// List current and upcoming batches, marking the one in session today
+    Batches {
+        /// Include past batches too
+        #[arg(long)]
+        all: bool,
+        /// Deprecated: use --output json
+        #[arg(long, hide = true)]
+        json: bool,
+    },
+    /// List the days you (or someone else) checked in over a date range
+    History(HistoryArgs),
+    /// Show how many people were checked in on each day of a date range
+    Range {
+        /// First day of the range (YYYY-MM-DD, today, yesterday, or e.g. 7d)
+        #[arg(value_parser = relative_date)]
+        start: String,
+        /// Last day of the range, inclusive (YYYY-MM-DD, today, yesterday, or e.g. 7d)
+        #[arg(value_parser = relative_date)]
+        end: String,
+        /// Deprecated: use --output text, json, or csv
+        #[arg(long, value_enum, hide = true)]
+        format: Option<RangeFormat>,
+    },
+    /// Summarize hub attendance over a date range
+    Summary {
+        /// First day of the range (YYYY-MM-DD, today, yesterday, or e.g. 7d)
+        #[arg(value_parser = relative_date)]
+        start: String,
+        /// Last day of the range, inclusive (YYYY-MM-DD, today, yesterday, or e.g. 7d)
+        #[arg(value_parser = relative_date)]
+        end: String,
+        /// Deprecated: use --output json
+        #[arg(long, hide = true)]
+        json: bool,
+    },
+    /// Show a month grid of your check-ins and hub attendance
+    Calendar {
+        /// Month to show (defaults to the current month, format: YYYY-MM)
         #[arg(short, long)]
+        month: Option<String>,
+        /// Use plain ASCII without unicode marks or color
+        #[arg(long)]
+        ascii: bool,
+    },
+    /// Check whether specific people are checked in
+    AreTheyIn {
+        /// Comma-separated person ids or names (names are looked up in the profile directory)
+        #[arg(value_delimiter = ',', required = true)]
+        people: Vec<String>,
+        /// Date to check (defaults to today; YYYY-MM-DD, yesterday, or e.g. 7d)
+        #[arg(short, long, value_parser = relative_date)]
+        date: Option<String>,
+    },
+    /// Exit 0 and print the matching names if someone whose name contains NAME is checked in today, exit 1 otherwise
+    IsIn {
+        /// Case-insensitive part of the person's name
+        name: String,
+    },
+    /// Count the #hashtags in your check-in notes
+    Tags {
+        /// Number of days to look back, including today
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+        #[command(flatten)]
+        range: RangeArgs,
+        /// Deprecated: use --output json
+        #[arg(long, hide = true)]
+        json: bool,
+    },
+    /// Save your Recurse API token to the config file (RC_TOKEN, then RC_TOKEN_FILE, take precedence over it)
+    Login {
+        /// Token to save (read from stdin if omitted)
+        token: Option<String>,
+    },
+    /// Inspect or edit tcurse configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Summarize a day's attendance and notes for sharing
+    Digest {
+        /// Date to summarize (defaults to today; YYYY-MM-DD, yesterday, or e.g. 7d)
+        #[arg(short, long, value_parser = relative_date)]
+        date: Option<String>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = DigestFormat::Plain)]
+        format: DigestFormat,
+    },
+    /// List the gaps between your check-ins
+    Gaps {
+        /// Number of days to look back, including today
+        #[arg(long, default_value_t = 90)]
+        days: u32,
+        #[command(flatten)]
+        range: RangeArgs,
+        /// Only show gaps of at least this many days
+        #[arg(long, default_value_t = 1)]
+        min: i64,
+        /// Deprecated: use --output json
+        #[arg(long, hide = true)]
+        json: bool,
+    },
+    /// Print hub attendance as Prometheus text-format metrics
+    Metrics {
+        /// Date to report (defaults to today; YYYY-MM-DD, yesterday, or e.g. 7d)
+        #[arg(short, long, value_parser = relative_date)]
         date: Option<String>,
+        /// Comma-separated person ids to emit per-person gauges for
+        #[arg(long, value_delimiter = ',')]
+        person: Vec<i64>,
+    },
+    /// Poll the roster and emit arrival/departure events until stopped
+    Daemon {
+        /// Seconds between polls
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(5..))]
+        interval: u64,
+        /// Where to send events
+        #[arg(long, value_enum, default_value_t = Sink::Stdout)]
+        sink: Sink,
+        /// URL to POST each event to as JSON (with --sink webhook)
+        #[arg(long, required_if_eq("sink", "webhook"))]
+        webhook_url: Option<String>,
+        /// Comma-separated person ids to watch (defaults to everyone)
+        #[arg(long, value_delimiter = ',')]
+        person: Vec<i64>,
+    },
+    /// Open a live dashboard of the roster where you can toggle your check-in and browse other days
+    Tui {
+        /// Seconds between automatic refreshes
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(5..))]
+        interval: u64,
+        /// Save notes even if they look like they contain a secret
+        #[arg(long)]
+        force: bool,
+    },
+    /// Fetch an API endpoint and print the shape of its JSON response
+    Inspect {
+        /// Path relative to the API base, e.g. profiles/me or "hub_visits?date=2024-01-15"
+        path: String,
+    },
+    /// Import historical check-ins from a CSV file with date,notes columns
+    Import {
+        /// CSV file to read
+        #[arg(long = "in", value_name = "FILE")]
+        input: PathBuf,
+        /// Import notes even if they look like they contain a secret
+        #[arg(long)]
+        force: bool,
+    },
+    /// Show your current and longest runs of consecutive check-in days
+    Streak {
+        /// Number of days to look back, including today
+        #[arg(long, default_value_t = 365)]
+        days: u32,
+        #[command(flatten)]
+        range: RangeArgs,
+        /// Ignore Saturdays and Sundays: they neither extend nor break a streak
+        #[arg(long)]
+        skip_weekends: bool,
+        /// Deprecated: use --output json
+        #[arg(long, hide = true)]
+        json: bool,
+    },
+    /// Check in for each YYYY-MM-DD date read from stdin, one per line, optionally followed by a tab and notes
+    Backfill {
+        /// Submit notes even if they look like they contain a secret
+        #[arg(long)]
+        force: bool,
+    },
+    /// List days where you were the only person checked in
+    Solo {
+        /// Number of days to look back, including today
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+        #[command(flatten)]
+        range: RangeArgs,
+        /// Deprecated: use --output json
+        #[arg(long, hide = true)]
+        json: bool,
     },
 }
 
-fn get_token() -> String {
-    dotenvy::dotenv().ok();
-    env::var("RC_TOKEN").expect("RC_TOKEN must be set (via environment or .env file)")
+#[derive(Args)]
+struct HistoryArgs {
+    /// First day of the range (YYYY-MM-DD, today, yesterday, or e.g. 7d)
+    #[arg(value_parser = relative_date)]
+    start: String,
+    /// Last day of the range, inclusive (YYYY-MM-DD, today, yesterday, or e.g. 7d)
+    #[arg(value_parser = relative_date)]
+    end: String,
+    /// Person id to look up (defaults to you)
+    #[arg(long)]
+    person: Option<i64>,
+    /// Only count days whose notes carry this hashtag (e.g. rust or #rust)
+    #[arg(long)]
+    tag: Option<String>,
+    /// Only list the earliest N days
+    #[arg(long, value_name = "N", conflicts_with = "last")]
+    first: Option<usize>,
+    /// Only list the most recent N days
+    #[arg(long, value_name = "N")]
+    last: Option<usize>,
+    /// Deprecated: use --output json
+    #[arg(long, hide = true)]
+    json: bool,
 }
 
-fn get_date_string(date_arg: Option<String>) -> String {
-    match date_arg {
-        Some(d) => d,
-        None => Local::now().format("%Y-%m-%d").to_string(),
+#[derive(Args)]
+struct CheckinArgs {
+    /// Optional notes to add to your check-in (defaults to TCURSE_DEFAULT_NOTES when set)
+    #[arg(short, long, group = "note_input")]
+    notes: Option<String>,
+    /// Read the notes from a file instead ("-" for stdin)
+    #[arg(long, value_name = "PATH", group = "note_input", conflicts_with = "notes")]
+    notes_file: Option<PathBuf>,
+    /// Date to check in for or remove (defaults to today; YYYY-MM-DD, yesterday, or e.g. 7d)
+    #[arg(short, long, conflicts_with = "from", value_parser = relative_date)]
+    date: Option<String>,
+    /// Prompt for notes when none are given and stdin is a terminal (an empty line means no notes)
+    #[arg(short, long, conflicts_with_all = ["note_input", "clear_notes", "remove"])]
+    interactive: bool,
+    /// Add the new notes to the end of your existing notes instead of replacing them (also enabled by TCURSE_APPEND_NOTES=true)
+    #[arg(long, requires = "note_input", conflicts_with = "remove")]
+    append: bool,
+    /// Clear the notes on an existing check-in (omitting --notes leaves them unchanged)
+    #[arg(long, conflicts_with_all = ["note_input", "append", "remove"])]
+    clear_notes: bool,
+    /// Remove your check-in instead of creating one
+    #[arg(short, long)]
+    remove: bool,
+    /// Fail instead of checking in without notes (also enabled by TCURSE_REQUIRE_NOTES=true)
+    #[arg(long)]
+    require_notes: bool,
+    /// First date of a range of check-ins to remove (YYYY-MM-DD, yesterday, or e.g. 7d)
+    #[arg(long, requires_all = ["remove", "to"], value_parser = relative_date)]
+    from: Option<String>,
+    /// Last date of a range of check-ins to remove (YYYY-MM-DD, today, yesterday, or e.g. 7d)
+    #[arg(long, requires = "from", value_parser = relative_date)]
+    to: Option<String>,
+    /// Skip the confirmation prompt when removing a range
+    #[arg(short, long, requires = "from")]
+    yes: bool,
+    /// Submit notes even if they look like they contain a secret
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Args)]
+struct RangeArgs {
+    /// Month or day to cover instead of --days (YYYY-MM or YYYY-MM-DD)
+    #[arg(long, conflicts_with_all = ["days", "since"])]
+    during: Option<String>,
+    /// How far back to look instead of --days, e.g. 14d, 2w, or 3m (months count as 30 days)
+    #[arg(long, conflicts_with = "days")]
+    since: Option<String>,
+}
+
+#[derive(Args)]
+struct CheckedInArgs {
+    /// Date to check (defaults to today; YYYY-MM-DD, yesterday, or e.g. 7d)
+    #[arg(short, long, value_parser = relative_date)]
+    date: Option<String>,
+    /// Show every day from this date instead of a single day (YYYY-MM-DD, yesterday, or e.g. 7d)
+    #[arg(long, conflicts_with_all = ["date", "by_period"], value_parser = relative_date)]
+    from: Option<String>,
+    /// Last day to show with --from, inclusive (defaults to today)
+    #[arg(long, requires = "from", value_parser = relative_date)]
+    to: Option<String>,
+    /// Only show your own entry from the roster
+    #[arg(long)]
+    mine: bool,
+    /// Leave yourself out of the roster
+    #[arg(long, conflicts_with = "mine")]
+    others: bool,
+    /// Only show people who checked in at or after this hub time (HH:MM)
+    #[arg(long, value_parser = parse_time)]
+    after: Option<NaiveTime>,
+    /// Only show people who checked in before this hub time (HH:MM)
+    #[arg(long, value_parser = parse_time)]
+    before: Option<NaiveTime>,
+    /// Group people into morning, afternoon, and evening by check-in time (boundaries set by TCURSE_PERIODS, default 12,17)
+    #[arg(long, conflicts_with = "mine")]
+    by_period: bool,
+    /// Text layout: list or table (defaults to list, or TCURSE_FORMAT); json and csv are deprecated in favor of --output
+    #[arg(long, value_enum)]
+    format: Option<RosterFormat>,
+    /// Deprecated: use --output json
+    #[arg(long, hide = true, conflicts_with = "format")]
+    json: bool,
+    /// Truncate notes longer than this many characters in table output
+    #[arg(long, default_value_t = 40)]
+    notes_width: usize,
+    /// Re-fetch and reprint the roster until stopped with Ctrl-C
+    #[arg(short, long)]
+    watch: bool,
+    /// Seconds between refreshes with --watch
+    #[arg(long, default_value_t = 60, requires = "watch", value_parser = clap::value_parser!(u64).range(5..))]
+    interval: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+    Csv,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RosterFormat {
+    List,
+    Table,
+    Json,
+    Csv,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RangeFormat {
+    Counts,
+    Json,
+    Csv,
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective configuration and where each value came from
+    Show {
+        /// Deprecated: use --output text or json
+        #[arg(long, value_enum, hide = true)]
+        format: Option<ConfigFormat>,
+    },
+    /// Print the values saved in the config file
+    List,
+    /// Print one value saved in the config file
+    Get {
+        /// Setting name, e.g. name_format
+        key: String,
+    },
+    /// Save a value to the config file
+    Set {
+        /// Setting name: token, name_format, format, color, retries, require_notes, default_notes, append_notes, allow_secrets, hub_hours, periods, circuit_breaker, idempotency_keys, person_id, api_base, or timezone
+        key: String,
+        /// Value to save
+        value: String,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ConfigFormat {
+    Table,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Sink {
+    Stdout,
+    Webhook,
+    Desktop,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum NameFormat {
+    Full,
+    First,
+    Initials,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DigestFormat {
+    Plain,
+    Markdown,
+}
+
+static MISSING_DIR_WARNING: Once = Once::new();
+
+fn tcurse_dir(base: Option<PathBuf>, kind: &str) -> Option<PathBuf> {
+    let dir = base.map(|d| d.join("tcurse"));
+    if dir.is_none() {
+        MISSING_DIR_WARNING.call_once(|| {
+            eprintln!(
+                "Warning: could not determine a {} directory; tcurse will run without its {} files. Use RC_TOKEN or --env-file instead.",
+                kind, kind
+            );
+        });
     }
+    dir
 }
 
-async fn checkin(client: &ApiClient, notes: Option<String>, remove: bool) -> Result<(), String> {
-    let me = client.get_current_user().await?;
-    let date = get_date_string(None);
+fn config_dir() -> Option<PathBuf> {
+    tcurse_dir(dirs::config_dir(), "config")
+}
 
-    if remove {
-        client.delete_visit(me.id, &date).await?;
-        println!("Removed check-in for {}", date);
-        return Ok(());
+fn load_env(env_file: Option<&Path>) -> Result<HashMap<String, String>, String> {
+    let mut files = Vec::new();
+    if let Some(path) = env_file {
+        let iter = dotenvy::from_path_iter(path).map_err(|e| format!("Failed to load {}: {}", path.display(), e))?;
+        files.push((path.display().to_string(), iter));
+    }
+    if let Ok(iter) = dotenvy::from_filename_iter(".env") {
+        files.push((".env".to_string(), iter));
+    }
+    if let Some(dir) = config_dir() {
+        let path = dir.join(".env");
+        if let Ok(iter) = dotenvy::from_path_iter(&path) {
+            files.push((path.display().to_string(), iter));
+        }
     }
 
-    // Check if already checked in (only block if no new notes to add)
-    if let Some(existing) = client.get_visit(me.id, &date).await? {
-        if notes.is_none() {
-            println!("Already checked in for {}", existing.date);
-            if let Some(n) = existing.notes {
-                if !n.is_empty() {
-                    println!("Notes: {}", n);
-                }
+    let mut sources = apply_env_files(files);
+
+    let config = read_config().unwrap_or_else(|e| {
+        eprintln!("Warning: ignoring the config file. {}", e);
+        toml::Table::new()
+    });
+    let source = config_file().map(|p| p.display().to_string()).unwrap_or_default();
+    for (key, var) in CONFIG_KEYS {
+        let value = match config.get(key) {
+            Some(toml::Value::String(s)) => s.clone(),
+            Some(other) => other.to_string(),
+            None => continue,
+        };
+        if env::var_os(var).is_none() {
+            env::set_var(var, value);
+            sources.insert(var.to_string(), source.clone());
+        }
+    }
+    Ok(sources)
+}
+
+fn apply_env_files(files: Vec<(String, dotenvy::Iter<File>)>) -> HashMap<String, String> {
+    let mut sources = HashMap::new();
+    for (source, iter) in files {
+        for (key, value) in iter.flatten() {
+            if env::var_os(&key).is_none() {
+                env::set_var(&key, value);
+                sources.insert(key, source.clone());
             }
-            return Ok(());
         }
     }
+    sources
+}
+
+const CONFIG_KEYS: [(&str, &str); 15] = [
+    ("name_format", "TCURSE_NAME_FORMAT"),
+    ("format", "TCURSE_FORMAT"),
+    ("color", "TCURSE_COLOR"),
+    ("retries", "TCURSE_RETRIES"),
+    ("require_notes", "TCURSE_REQUIRE_NOTES"),
+    ("default_notes", "TCURSE_DEFAULT_NOTES"),
+    ("append_notes", "TCURSE_APPEND_NOTES"),
+    ("hub_hours", "TCURSE_HUB_HOURS"),
+    ("periods", "TCURSE_PERIODS"),
+    ("circuit_breaker", "TCURSE_CIRCUIT_BREAKER"),
+    ("idempotency_keys", "TCURSE_IDEMPOTENCY_KEYS"),
+    ("person_id", "TCURSE_PERSON_ID"),
+    ("api_base", "TCURSE_API_BASE"),
+    ("allow_secrets", "TCURSE_ALLOW_SECRETS"),
+    ("timezone", "TCURSE_TIMEZONE"),
+];
+
+fn parse_config_value(key: &str, raw: &str) -> Result<toml::Value, String> {
+    let raw = raw.trim();
+    let invalid = |expected: &str| format!("Invalid value '{}' for {}. Use {}", raw, key, expected);
+    let enum_value = |value: Option<clap::builder::PossibleValue>| toml::Value::String(value.unwrap().get_name().to_string());
+    match key {
+        "name_format" => NameFormat::from_str(raw, true)
+            .map(|v| enum_value(v.to_possible_value()))
+            .map_err(|_| invalid("full, first, or initials")),
+        "format" => RosterFormat::from_str(raw, true)
+            .map(|v| enum_value(v.to_possible_value()))
+            .map_err(|_| invalid("list, table, json, or csv")),
+        "color" => ColorChoice::from_str(raw, true)
+            .map(|v| enum_value(v.to_possible_value()))
+            .map_err(|_| invalid("auto, always, or never")),
+        "retries" => match raw.parse::<u32>() {
+            Ok(n) if n <= MAX_RETRIES => Ok(toml::Value::Integer(n.into())),
+            _ => Err(invalid(&format!("a number from 0 to {}", MAX_RETRIES))),
+        },
+        "person_id" => match raw.parse::<i64>() {
+            Ok(id) if id > 0 => Ok(toml::Value::Integer(id)),
+            _ => Err(invalid("your positive numeric person id")),
+        },
+        "timezone" => parse_timezone(raw).map(|tz| toml::Value::String(tz.name().to_string())),
+        "require_notes" | "append_notes" | "allow_secrets" | "idempotency_keys" => match raw.to_lowercase().as_str() {
+            "1" | "true" | "yes" => Ok(toml::Value::Boolean(true)),
+            "0" | "false" | "no" => Ok(toml::Value::Boolean(false)),
+            _ => Err(invalid("true or false")),
+        },
+        "token" | "hub_hours" | "periods" | "circuit_breaker" | "default_notes" | "api_base" if raw.is_empty() => Err(format!("{} can't be empty", key)),
+        "token" | "hub_hours" | "periods" | "circuit_breaker" | "default_notes" | "api_base" => Ok(toml::Value::String(raw.to_string())),
+        _ => Err(unknown_config_key(key)),
+    }
+}
+
+fn unknown_config_key(key: &str) -> String {
+    let keys: Vec<&str> = std::iter::once("token").chain(CONFIG_KEYS.iter().map(|(k, _)| *k)).collect();
+    format!("Unknown setting '{}'. Use one of: {}", key, keys.join(", "))
+}
+
+fn config_value_string(key: &str, value: &toml::Value) -> String {
+    match value {
+        _ if key == "token" => "[redacted]".to_string(),
+        toml::Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
 
-    let visit = client.create_or_update_visit(me.id, &date, notes.as_deref()).await?;
+fn config_list() -> Result<(), String> {
+    let config = read_config()?;
+    if config.is_empty() {
+        match config_file() {
+            Some(path) => println!("No settings saved in {}", path.display()),
+            None => println!("No settings saved"),
+        }
+        return Ok(());
+    }
+    for (key, value) in &config {
+        outln!("{} = {}", key, config_value_string(key, value));
+    }
+    Ok(())
+}
 
-    println!("Checked in for {}", visit.date);
-    if let Some(n) = visit.notes {
-        if !n.is_empty() {
-            println!("Notes: {}", n);
+fn config_get(key: &str) -> Result<(), String> {
+    if key != "token" && !CONFIG_KEYS.iter().any(|(k, _)| *k == key) {
+        return Err(unknown_config_key(key));
+    }
+    match read_config()?.get(key) {
+        Some(value) => {
+            outln!("{}", config_value_string(key, value));
+            Ok(())
         }
+        None => Err(format!("{} is not set in the config file", key)),
     }
+}
 
+fn config_set(key: &str, value: &str) -> Result<(), String> {
+    let value = parse_config_value(key, value)?;
+    let path = config_file().ok_or("Could not determine a config directory; use environment variables instead")?;
+    let shown = config_value_string(key, &value);
+    save_config_value(&path, key, value)?;
+    println!("Set {} = {} in {}", key, shown, path.display());
     Ok(())
 }
 
-async fn get_checked_in(client: &ApiClient, date: Option<String>) -> Result<(), String> {
-    let date_str = get_date_string(date);
+fn env_source(key: &str, sources: &HashMap<String, String>) -> Option<String> {
+    match sources.get(key) {
+        Some(file) => Some(file.clone()),
+        None if env::var_os(key).is_some() => Some("environment".to_string()),
+        None => None,
+    }
+}
 
-    // Validate date format
-    NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
-        .map_err(|_| "Invalid date format. Use YYYY-MM-DD".to_string())?;
+fn resolve_name_format(flag: Option<NameFormat>) -> Result<NameFormat, String> {
+    if let Some(format) = flag {
+        return Ok(format);
+    }
+    match env::var("TCURSE_NAME_FORMAT") {
+        Ok(v) => NameFormat::from_str(v.trim(), true)
+            .map_err(|_| format!("Invalid TCURSE_NAME_FORMAT '{}'. Use full, first, or initials", v)),
+        Err(_) => Ok(NameFormat::Full),
+    }
+}
 
-    let visits = client.get_visits(&date_str).await?;
+fn resolve_retries(cli: &Cli) -> Result<u32, String> {
+    if cli.no_retry {
+        return Ok(0);
+    }
+    if let Some(retries) = cli.retries {
+        return Ok(retries);
+    }
+    match env::var("TCURSE_RETRIES") {
+        Ok(v) => match v.trim().parse() {
+            Ok(n) if n <= MAX_RETRIES => Ok(n),
+            _ => Err(format!("Invalid TCURSE_RETRIES '{}'. Use a number from 0 to {}", v, MAX_RETRIES)),
+        },
+        Err(_) => Ok(0),
+    }
+}
 
-    if visits.is_empty() {
-        println!("No one is checked in for {}", date_str);
-        return Ok(());
+fn resolve_roster_format(flag: Option<RosterFormat>) -> Result<RosterFormat, String> {
+    if let Some(format) = flag {
+        return Ok(format);
+    }
+    match env::var("TCURSE_FORMAT") {
+        Ok(v) => RosterFormat::from_str(v.trim(), true)
+            .map_err(|_| format!("Invalid TCURSE_FORMAT '{}'. Use list, table, json, or csv", v)),
+        Err(_) => Ok(RosterFormat::List),
     }
+}
 
-    println!("Checked in for {} ({} people):", date_str, visits.len());
-    for visit in visits {
-        let name = &visit.person.name;
-        match &visit.notes {
-            Some(n) if !n.is_empty() => println!("  - {} ({})", name, n),
-            _ => println!("  - {}", name),
+fn output_name(output: OutputFormat) -> String {
+    output.to_possible_value().unwrap().get_name().to_string()
+}
+
+fn deprecated_output(command: &Commands) -> Option<(&'static str, OutputFormat)> {
+    match command {
+        Commands::Whoami { json: true }
+        | Commands::Find { json: true, .. }
+        | Commands::Who { json: true, .. }
+        | Commands::Batches { json: true, .. }
+        | Commands::History(HistoryArgs { json: true, .. })
+        | Commands::Summary { json: true, .. }
+        | Commands::Tags { json: true, .. }
+        | Commands::Gaps { json: true, .. }
+        | Commands::Streak { json: true, .. }
+        | Commands::Solo { json: true, .. }
+        | Commands::CheckedIn(CheckedInArgs { json: true, .. }) => Some(("--json", OutputFormat::Json)),
+        Commands::CheckedIn(CheckedInArgs { format: Some(RosterFormat::Json), .. }) => Some(("--format json", OutputFormat::Json)),
+        Commands::CheckedIn(CheckedInArgs { format: Some(RosterFormat::Csv), .. }) => Some(("--format csv", OutputFormat::Csv)),
+        Commands::Range { format: Some(format), .. } => Some(match format {
+            RangeFormat::Counts => ("--format counts", OutputFormat::Text),
+            RangeFormat::Json => ("--format json", OutputFormat::Json),
+            RangeFormat::Csv => ("--format csv", OutputFormat::Csv),
+        }),
+        Commands::Config { command: ConfigCommand::Show { format: Some(format) } } => Some(match format {
+            ConfigFormat::Table => ("--format table", OutputFormat::Text),
+            ConfigFormat::Json => ("--format json", OutputFormat::Json),
+        }),
+        _ => None,
+    }
+}
+
+static DEPRECATED_OUTPUT_WARNING: Once = Once::new();
+
+fn output_format(cli: &Cli) -> Result<Option<OutputFormat>, String> {
+    let output = match (cli.output, deprecated_output(&cli.command)) {
+        (Some(output), Some((flag, implied))) if output != implied => {
+            return Err(format!("{} conflicts with --output {}", flag, output_name(output)));
         }
+        (None, Some((flag, implied))) => {
+            if !cli.quiet {
+                DEPRECATED_OUTPUT_WARNING.call_once(|| {
+                    eprintln!("Warning: {} is deprecated; use --output {}", flag, output_name(implied));
+                });
+            }
+            Some(implied)
+        }
+        (output, _) => output,
+    };
+    if output == Some(OutputFormat::Csv) && !matches!(cli.command, Commands::Range { .. } | Commands::CheckedIn(_)) {
+        return Err("--output csv is only supported by range and checked-in; use text or json".to_string());
     }
+    Ok(output)
+}
 
-    Ok(())
+fn roster_format(output: Option<OutputFormat>, flag: Option<RosterFormat>) -> Result<RosterFormat, String> {
+    Ok(match output {
+        Some(OutputFormat::Json) => RosterFormat::Json,
+        Some(OutputFormat::Csv) => RosterFormat::Csv,
+        Some(OutputFormat::Text) => match resolve_roster_format(flag)? {
+            RosterFormat::Table => RosterFormat::Table,
+            _ => RosterFormat::List,
+        },
+        None => resolve_roster_format(flag)?,
+    })
 }
 
-#[tokio::main]
-async fn main() {
-    let cli = Cli::parse();
-    let token = get_token();
-    let client = ApiClient::new(token);
+fn resolve_color(flag: Option<ColorChoice>) -> Result<ColorChoice, String> {
+    if let Some(choice) = flag {
+        return Ok(choice);
+    }
+    match env::var("TCURSE_COLOR") {
+        Ok(v) => ColorChoice::from_str(v.trim(), true)
+            .map_err(|_| format!("Invalid TCURSE_COLOR '{}'. Use auto, always, or never", v)),
+        Err(_) => Ok(ColorChoice::Auto),
+    }
+}
+
+fn format_name(full: &str, style: NameFormat) -> String {
+    let mut parts = full.split_whitespace();
+    match style {
+        NameFormat::Full => full.trim().to_string(),
+        NameFormat::First => parts.next().unwrap_or_default().to_string(),
+        NameFormat::Initials => parts
+            .filter_map(|p| p.chars().next())
+            .map(|c| format!("{}.", c.to_uppercase()))
+            .collect(),
+    }
+}
+
+fn configured_person_id() -> Result<Option<i64>, String> {
+    match env::var("TCURSE_PERSON_ID") {
+        Ok(v) => match v.trim().parse::<i64>() {
+            Ok(id) if id > 0 => Ok(Some(id)),
+            _ => Err(format!("Invalid TCURSE_PERSON_ID '{}'. Use your positive numeric person id", v)),
+        },
+        Err(_) => Ok(None),
+    }
+}
 
-    let result = match cli.command {
-        Commands::Checkin { notes, remove } => checkin(&client, notes, remove).await,
-        Commands::CheckedIn { date } => get_checked_in(&client, date).await,
+fn init_logging(verbose: u8) {
+    use tracing_subscriber::prelude::*;
+
+    let level = match verbose {
+        0 => return,
+        1 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
     };
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr).with_ansi(std::io::stderr().is_terminal()).with_target(false))
+        .with(tracing_subscriber::filter::Targets::new().with_target("tcurse", level))
+        .init();
+}
 
-    if let Err(e) = result {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+fn config_file() -> Option<PathBuf> {
+    config_dir().map(|d| d.join("config.toml"))
+}
+
+fn read_config() -> Result<toml::Table, String> {
+    let path = match config_file() {
+        Some(path) => path,
+        None => return Ok(toml::Table::new()),
+    };
+    match std::fs::read_to_string(&path) {
+        Ok(text) => text.parse().map_err(|e| format!("Failed to parse {}: {}", path.display(), e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(toml::Table::new()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+fn save_config_value(path: &Path, key: &str, value: toml::Value) -> Result<(), String> {
+    let mut config: toml::Table = match std::fs::read_to_string(path) {
+        Ok(text) => text.parse().unwrap_or_else(|e| {
+            eprintln!("Warning: replacing {}, which could not be parsed: {}", path.display(), e);
+            toml::Table::new()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => toml::Table::new(),
+        Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+    };
+    config.insert(key.to_string(), value);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    write_private(path, &config.to_string()).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents.as_bytes())
+}
+
+fn get_token() -> Result<String, String> {
+    if let Ok(token) = env::var("RC_TOKEN") {
+        return Ok(token);
+    }
+    if let Some(path) = env::var_os("RC_TOKEN_FILE") {
+        let path = PathBuf::from(path);
+        let token = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read RC_TOKEN_FILE {}: {}", path.display(), e))?;
+        let token = token.trim();
+        if token.is_empty() {
+            return Err(format!("RC_TOKEN_FILE {} is empty", path.display()));
+        }
+        return Ok(token.to_string());
+    }
+    match read_config()?.get("token").and_then(|v| v.as_str()) {
+        Some(token) => Ok(token.to_string()),
+        None => Err("RC_TOKEN or RC_TOKEN_FILE must be set (via environment, --env-file, .env file, or `tcurse login`)".to_string()),
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProfileCache {
+    token_hash: String,
+    profile: Profile,
+}
+
+fn profile_cache_file() -> Option<PathBuf> {
+    tcurse_dir(dirs::cache_dir(), "cache").map(|d| d.join("profile.json"))
+}
+
+fn token_hash(token: &str) -> String {
+    use sha2::{Digest, Sha256};
+    Sha256::digest(token.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn read_profile_cache(token: &str) -> Option<Profile> {
+    let contents = std::fs::read_to_string(profile_cache_file()?).ok()?;
+    let cache: ProfileCache = serde_json::from_str(&contents).ok()?;
+    (cache.token_hash == token_hash(token)).then_some(cache.profile)
+}
+
+fn write_profile_cache(token: &str, profile: &Profile) {
+    let Some(path) = profile_cache_file() else { return };
+    let cache = ProfileCache { token_hash: token_hash(token), profile: profile.clone() };
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|_| write_private(&path, &serde_json::to_string(&cache).unwrap()));
+    if let Err(e) = written {
+        eprintln!("Warning: could not write profile cache {}: {}", path.display(), e);
+    }
+}
+
+fn clear_profile_cache() {
+    if let Some(path) = profile_cache_file() {
+        std::fs::remove_file(path).ok();
+    }
+}
+
+fn login(token: Option<String>) -> Result<(), String> {
+    let token = match token {
+        Some(token) => token,
+        None => {
+            eprint!("Recurse API token: ");
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line).map_err(|e| format!("Failed to read token: {}", e))?;
+            line
+        }
+    };
+    let token = token.trim();
+    if token.is_empty() {
+        return Err("No token given".to_string());
+    }
+
+    let path = config_file().ok_or("Could not determine a config directory; set RC_TOKEN instead")?;
+    save_config_value(&path, "token", toml::Value::String(token.to_string()))?;
+    println!("Saved token to {}", path.display());
+    Ok(())
+}
+
+fn get_date_string(date_arg: Option<String>) -> String {
+    match date_arg {
+        Some(d) => d,
+        None => tcurse::hub_today().format("%Y-%m-%d").to_string(),
+    }
+}
+
+fn relative_date(s: &str) -> Result<String, String> {
+    relative_date_on(s, tcurse::hub_today())
+}
+
+fn relative_date_on(s: &str, today: NaiveDate) -> Result<String, String> {
+    Ok(tcurse::parse_relative_date(s, today)?.format("%Y-%m-%d").to_string())
+}
+
+fn parse_date(s: &str) -> Result<NaiveDate, String> {
+    parse_date_on(s, tcurse::hub_today())
+}
+
+fn parse_date_on(s: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let date = NaiveDate::parse_from_str(s.trim(), "%Y-%m-%d").map_err(|_| format!("Invalid date '{}'. Use YYYY-MM-DD", s))?;
+    if date.year() < FIRST_HUB_YEAR {
+        return Err(format!("{} is before the Recurse Center existed; use a date in {} or later", s, FIRST_HUB_YEAR));
+    }
+    if date > today {
+        return Err(format!("{} is in the future", s));
+    }
+    Ok(date)
+}
+
+fn env_flag(name: &str) -> bool {
+    matches!(
+        env::var(name).map(|v| v.trim().to_lowercase()).as_deref(),
+        Ok("1" | "true" | "yes")
+    )
+}
+
+fn parse_timezone(raw: &str) -> Result<Tz, String> {
+    raw.trim()
+        .parse::<Tz>()
+        .map_err(|_| format!("Invalid timezone '{}'. Use an IANA name, e.g. America/New_York or Europe/Berlin", raw))
+}
+
+fn hub_timezone() -> Result<Tz, String> {
+    match env::var("TCURSE_TIMEZONE") {
+        Ok(raw) => parse_timezone(&raw).map_err(|e| format!("TCURSE_TIMEZONE: {}", e)),
+        Err(_) => Ok(tcurse::DEFAULT_HUB_TZ),
+    }
+}
+
+fn hub_hours() -> Result<(u32, u32), String> {
+    let raw = match env::var("TCURSE_HUB_HOURS") {
+        Ok(v) => v,
+        Err(_) => return Ok((7, 22)),
+    };
+    let invalid = || format!("Invalid TCURSE_HUB_HOURS '{}'. Use START-END in hours, e.g. 7-22", raw);
+    let (start, end) = raw.split_once('-').ok_or_else(invalid)?;
+    let start: u32 = start.trim().parse().map_err(|_| invalid())?;
+    let end: u32 = end.trim().parse().map_err(|_| invalid())?;
+    if start >= end || end > 24 {
+        return Err(invalid());
+    }
+    Ok((start, end))
+}
+
+fn off_hours_note(now: DateTime<Tz>, (start, end): (u32, u32)) -> Option<String> {
+    if matches!(now.weekday(), Weekday::Sat | Weekday::Sun) {
+        return Some(format!("Heads up: it's {} in the hub", now.format("%A")));
+    }
+    if now.hour() < start || now.hour() >= end {
+        return Some(format!("Heads up: it's {} in the hub", now.format("%H:%M")));
+    }
+    None
+}
+
+fn secret_reason(notes: &str) -> Option<&'static str> {
+    let lower = notes.to_lowercase();
+    for key in ["token=", "password=", "passwd=", "secret=", "api_key=", "apikey="] {
+        if let Some(pos) = lower.find(key) {
+            if lower[pos + key.len()..].starts_with(|c: char| !c.is_whitespace()) {
+                return Some("an assignment to a token, password, or key");
+            }
+        }
+    }
+
+    for word in notes.split(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | ',' | ';' | '(' | ')')) {
+        if ["ghp_", "gho_", "github_pat_", "xoxb-", "xoxp-", "sk-"].iter().any(|p| word.starts_with(p)) && word.len() >= 20 {
+            return Some("a known API token prefix");
+        }
+        if word.len() == 20 && word.starts_with("AKIA") && word.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+            return Some("an AWS access key id");
+        }
+        if word.len() >= 32 && word.chars().all(|c| c.is_ascii_hexdigit()) && word.chars().any(|c| c.is_ascii_digit()) {
+            return Some("a long hex string");
+        }
+        if word.len() >= 40
+            && word.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '=' | '-' | '_'))
+            && word.chars().any(|c| c.is_ascii_digit())
+            && word.chars().any(|c| c.is_ascii_uppercase())
+            && word.chars().any(|c| c.is_ascii_lowercase())
+        {
+            return Some("a long base64-like string");
+        }
+    }
+    None
+}
+
+fn check_secrets(notes: Option<&str>, allowed: bool) -> Result<(), String> {
+    match notes.and_then(secret_reason) {
+        Some(reason) if !allowed => Err(format!(
+            "Your notes contain what looks like {}. Check-in notes are visible to others; pass --force if this is intended",
+            reason
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn checkin_notes(notes: Option<String>, default: Option<String>, required: bool) -> Result<Option<String>, String> {
+    let notes = notes.or(default);
+    if required && notes.as_deref().is_none_or(|n| n.trim().is_empty()) {
+        return Err("Notes are required for check-in. Pass --notes \"...\" or set default_notes".to_string());
+    }
+    Ok(notes)
+}
+
+struct CheckinSettings {
+    require_notes: bool,
+    default_notes: Option<String>,
+    append_notes: bool,
+    allow_secrets: bool,
+    hub_hours: (u32, u32),
+}
+
+impl CheckinSettings {
+    fn from_env() -> Result<Self, String> {
+        Ok(CheckinSettings {
+            require_notes: env_flag("TCURSE_REQUIRE_NOTES"),
+            default_notes: env::var("TCURSE_DEFAULT_NOTES").ok(),
+            append_notes: env_flag("TCURSE_APPEND_NOTES"),
+            allow_secrets: env_flag("TCURSE_ALLOW_SECRETS"),
+            hub_hours: hub_hours()?,
+        })
+    }
+}
+
+async fn checkin(
+    client: &ApiClient,
+    args: CheckinArgs,
+    settings: &CheckinSettings,
+    quiet: bool,
+    dry_run: bool,
+    json: bool,
+) -> Result<(), CliError> {
+    let CheckinArgs { mut notes, notes_file, date, interactive, append, clear_notes, remove, require_notes, force, .. } = args;
+    if let Some(path) = &notes_file {
+        notes = Some(read_notes_file(path)?);
+    }
+    if interactive && std::io::stdin().is_terminal() {
+        notes = prompt_notes()?;
+    }
+    let backfill = date.is_some();
+    let date = get_date_string(date);
+
+    // Validate date format
+    parse_date(&date)?;
+
+    let require_notes = require_notes || settings.require_notes;
+    let new_visit_notes = match remove || clear_notes {
+        true => None,
+        false => checkin_notes(notes.clone(), settings.default_notes.clone(), require_notes)?,
+    };
+    check_secrets(notes.as_deref(), force || settings.allow_secrets)?;
+    let my_id = client.current_user_id().await?;
+
+    if remove {
+        if dry_run {
+            println!("[dry-run] Would remove the check-in for person {} on {}", my_id, date);
+            return Ok(());
+        }
+        client.delete_visit(my_id, &date).await?;
+        if json {
+            print_json(&serde_json::json!({ "date": date, "removed": true }), "removal")?;
+        } else if !quiet {
+            println!("Removed check-in for {}", date);
+        }
+        return Ok(());
+    }
+
+    if !quiet && !backfill {
+        if let Some(note) = off_hours_note(tcurse::hub_now(), settings.hub_hours) {
+            eprintln!("{}", note);
+        }
+    }
+
+    if clear_notes {
+        let existing = match client.get_visit(my_id, &date).await? {
+            Some(existing) => existing,
+            None => return Err(format!("You aren't checked in for {}, so there are no notes to clear", date).into()),
+        };
+        if existing.notes.as_deref().is_none_or(|n| n.is_empty()) {
+            if json {
+                print_json(&existing, "visit")?;
+            } else if !quiet {
+                println!("No notes to clear for {}", date);
+            }
+            return Ok(());
+        }
+        if dry_run {
+            println!("[dry-run] Would clear the notes on person {}'s check-in for {}", my_id, date);
+            return Ok(());
+        }
+        let visit = client.create_or_update_visit(my_id, &date, Some("")).await?;
+        if json {
+            print_json(&visit, "visit")?;
+        } else if !quiet {
+            println!("Cleared notes for {}", date);
+        }
+        return Ok(());
+    }
+
+    // Check if already checked in (only block if no new notes to add)
+    if let Some(existing) = client.get_visit(my_id, &date).await? {
+        if notes.is_none() {
+            return Ok(report_checkin("Already checked in", &existing, quiet, json)?);
+        }
+        if append || settings.append_notes {
+            if let Some(old) = existing.notes.filter(|n| !n.trim().is_empty()) {
+                notes = notes.map(|n| format!("{}\n{}", old, n));
+            }
+        }
+    }
+    let notes = notes.or(new_visit_notes);
+
+    if dry_run {
+        println!("[dry-run] Would check in person {} for {}", my_id, date);
+        if let Some(n) = notes.as_deref().filter(|n| !n.is_empty()) {
+            println!("[dry-run] Notes: {}", n);
+        }
+        return Ok(());
+    }
+
+    let visit = client.create_or_update_visit(my_id, &date, notes.as_deref()).await?;
+    Ok(report_checkin("Checked in", &visit, quiet, json)?)
+}
+
+fn prompt_notes() -> Result<Option<String>, String> {
+    eprint!("Notes (leave empty for none): ");
+    let mut line = String::new();
+    std::io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| format!("Failed to read notes: {}", e))?;
+    let line = line.trim();
+    Ok((!line.is_empty()).then(|| line.to_string()))
+}
+
+fn read_notes_file(path: &Path) -> Result<String, String> {
+    let text = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin()).map_err(|e| format!("Failed to read notes from stdin: {}", e))?
+    } else {
+        std::fs::read_to_string(path).map_err(|e| format!("Failed to read notes from {}: {}", path.display(), e))?
+    };
+    let text = text.strip_suffix('\n').unwrap_or(&text);
+    Ok(text.strip_suffix('\r').unwrap_or(text).to_string())
+}
+
+fn report_checkin(status: &str, visit: &HubVisit, quiet: bool, json: bool) -> Result<(), String> {
+    if json {
+        return print_json(visit, "visit");
+    }
+    if !quiet {
+        print_checkin(status, visit);
+    }
+    Ok(())
+}
+
+fn print_checkin(status: &str, visit: &HubVisit) {
+    println!("{} for {}", status, visit.date);
+    if let Some(n) = visit.notes.as_deref().filter(|n| !n.is_empty()) {
+        println!("Notes: {}", n);
+    }
+}
+
+fn parse_time(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s, "%H:%M").map_err(|_| "Invalid time format. Use HH:MM".to_string())
+}
+
+fn filter_by_time(visits: Vec<HubVisit>, after: Option<NaiveTime>, before: Option<NaiveTime>, tz: Tz) -> Result<Vec<HubVisit>, String> {
+    if after.is_none() && before.is_none() {
+        return Ok(visits);
+    }
+
+    let mut filtered = Vec::new();
+    for visit in visits {
+        let time = visit
+            .created_at
+            .as_deref()
+            .and_then(tcurse::parse_timestamp)
+            .map(|dt| dt.with_timezone(&tz).time())
+            .ok_or_else(|| "Check-in times are not available for this roster, so --after/--before can't be applied".to_string())?;
+        if after.is_some_and(|a| time < a) || before.is_some_and(|b| time >= b) {
+            continue;
+        }
+        filtered.push(visit);
+    }
+    Ok(filtered)
+}
+
+fn confirm(prompt: &str) -> Result<bool, String> {
+    if !std::io::stdin().is_terminal() {
+        return Err("Refusing to continue without confirmation. Pass --yes to proceed".to_string());
+    }
+    eprint!("{} [y/N] ", prompt);
+    let mut answer = String::new();
+    std::io::stdin()
+        .read_line(&mut answer)
+        .map_err(|e| format!("Failed to read confirmation: {}", e))?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+async fn remove_range(client: &ApiClient, from: &str, to: &str, yes: bool, dry_run: bool, json: bool) -> Result<(), CliError> {
+    let start = parse_date(from)?;
+    let end = parse_date(to)?;
+    if start > end {
+        return Err(format!("--from {} is after --to {}", from, to).into());
+    }
+
+    let my_id = client.current_user_id().await?;
+    let dates: Vec<NaiveDate> = client
+        .attendance_calendar(my_id, start, end)
+        .await?
+        .into_iter()
+        .filter(|(_, present)| *present)
+        .map(|(date, _)| date)
+        .collect();
+
+    if dates.is_empty() {
+        if json {
+            return Ok(print_json(&dates, "removals")?);
+        }
+        println!("No check-ins to remove between {} and {}", from, to);
+        return Ok(());
+    }
+
+    if dry_run {
+        for date in &dates {
+            println!("[dry-run] Would remove check-in for {}", date.format("%Y-%m-%d"));
+        }
+        return Ok(());
+    }
+
+    if !yes && !confirm(&format!("Remove {} check-ins between {} and {}?", dates.len(), from, to))? {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    let mut removals = Vec::new();
+    for date in &dates {
+        let date = date.format("%Y-%m-%d").to_string();
+        let error = client.delete_visit(my_id, &date).await.err().map(|e| e.to_string());
+        if !json {
+            match &error {
+                None => println!("  - {}: removed", date),
+                Some(e) => println!("  - {}: failed ({})", date, e),
+            }
+        }
+        if error.is_some() {
+            failed += 1;
+        }
+        removals.push(serde_json::json!({ "date": date, "removed": error.is_none(), "error": error }));
+    }
+
+    if json {
+        print_json(&removals, "removals")?;
+    } else {
+        println!("Removed {} of {} check-ins", dates.len() - failed, dates.len());
+    }
+    if failed > 0 {
+        return Err(format!("{} removals failed", failed).into());
+    }
+
+    Ok(())
+}
+
+fn print_visit(visit: &HubVisit, name_format: NameFormat) {
+    let name = paint(&format_name(&visit.person.name, name_format), BOLD);
+    match &visit.notes {
+        Some(n) if !n.is_empty() => outln!("  - {} {}", name, paint(&format!("({})", n), DIM)),
+        _ => outln!("  - {}", name),
+    }
+}
+
+fn period_bounds() -> Result<(u32, u32), String> {
+    parse_period_bounds(env::var("TCURSE_PERIODS").ok().as_deref())
+}
+
+fn parse_period_bounds(raw: Option<&str>) -> Result<(u32, u32), String> {
+    let raw = match raw {
+        Some(v) => v,
+        None => return Ok((12, 17)),
+    };
+    let invalid = || format!("Invalid TCURSE_PERIODS '{}'. Use AFTERNOON,EVENING start hours, e.g. 12,17", raw);
+    let (afternoon, evening) = raw.split_once(',').ok_or_else(invalid)?;
+    let afternoon: u32 = afternoon.trim().parse().map_err(|_| invalid())?;
+    let evening: u32 = evening.trim().parse().map_err(|_| invalid())?;
+    if afternoon >= evening || evening > 24 {
+        return Err(invalid());
+    }
+    Ok((afternoon, evening))
+}
+
+fn circuit_breaker() -> Result<(u32, u64), String> {
+    let raw = match env::var("TCURSE_CIRCUIT_BREAKER") {
+        Ok(v) => v,
+        Err(_) => return Ok((5, 30)),
+    };
+    let invalid = || format!("Invalid TCURSE_CIRCUIT_BREAKER '{}'. Use FAILURES,COOLDOWN_SECONDS, e.g. 5,30", raw);
+    let (failures, cooldown) = raw.split_once(',').ok_or_else(invalid)?;
+    let failures: u32 = failures.trim().parse().map_err(|_| invalid())?;
+    let cooldown: u64 = cooldown.trim().parse().map_err(|_| invalid())?;
+    if failures == 0 {
+        return Err(invalid());
+    }
+    Ok((failures, cooldown))
+}
+
+fn group_by_period(visits: &[HubVisit], (afternoon, evening): (u32, u32), tz: Tz) -> Result<[(&'static str, Vec<&HubVisit>); 3], String> {
+    let mut groups = [("Morning", Vec::new()), ("Afternoon", Vec::new()), ("Evening", Vec::new())];
+    for visit in visits {
+        let hour = visit
+            .created_at
+            .as_deref()
+            .and_then(tcurse::parse_timestamp)
+            .map(|dt| dt.with_timezone(&tz).hour())
+            .ok_or_else(|| "Check-in times are not available for this roster, so --by-period can't be applied".to_string())?;
+        let index = if hour < afternoon {
+            0
+        } else if hour < evening {
+            1
+        } else {
+            2
+        };
+        groups[index].1.push(visit);
+    }
+    Ok(groups)
+}
+
+const REVOKED_TOKEN: &str = "Your token was rejected — it may have been revoked; re-run `tcurse login`";
+
+fn unauthorized(error: &CliError) -> bool {
+    matches!(error, CliError::Api(ApiError::Http { status, .. }) if status.as_u16() == 401)
+}
+
+fn revoked_token(error: &CliError, succeeded_before: bool) -> Option<CliError> {
+    (unauthorized(error) && succeeded_before).then(|| CliError::Auth(REVOKED_TOKEN.to_string()))
+}
+
+async fn get_checked_in(
+    client: &ApiClient,
+    args: CheckedInArgs,
+    output: Option<OutputFormat>,
+    name_format: NameFormat,
+    quiet: bool,
+) -> Result<(), CliError> {
+    if !args.watch {
+        return show_checked_in(client, &args, output, name_format, quiet).await;
+    }
+
+    parse_date(&get_date_string(args.date.clone()))?;
+    let clear = OUTPUT_FILE.get().is_none() && std::io::stdout().is_terminal();
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs(args.interval));
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut succeeded = false;
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = ticker.tick() => {}
+        }
+        if clear {
+            out!("\x1b[2J\x1b[H");
+        }
+        match show_checked_in(client, &args, output, name_format, quiet).await {
+            Ok(()) => succeeded = true,
+            Err(e) => match revoked_token(&e, succeeded) {
+                Some(revoked) => return Err(revoked),
+                None => eprintln!("Error: {}", e),
+            },
+        }
+    }
+    Ok(())
+}
+
+async fn show_checked_in(
+    client: &ApiClient,
+    args: &CheckedInArgs,
+    output: Option<OutputFormat>,
+    name_format: NameFormat,
+    quiet: bool,
+) -> Result<(), CliError> {
+    if let Some(from) = &args.from {
+        return show_checked_in_range(client, args, from, output, name_format, quiet).await;
+    }
+    let date_str = get_date_string(args.date.clone());
+
+    // Validate date format
+    parse_date(&date_str)?;
+
+    let bounds = if args.by_period { Some(period_bounds()?) } else { None };
+    let format = roster_format(output, args.format)?;
+    let mut visits = filter_by_time(client.get_visits(&date_str).await?, args.after, args.before, tcurse::hub_tz())?;
+    let total = visits.len();
+    if args.others {
+        let my_id = client.current_user_id().await?;
+        visits.retain(|v| v.person.id != my_id);
+    }
+
+    if args.mine {
+        let my_id = client.current_user_id().await?;
+        visits = vec![my_entry(visits, my_id, &date_str)?];
+    }
+
+    if matches!(format, RosterFormat::Json | RosterFormat::Csv) {
+        if format == RosterFormat::Csv {
+            out!("{}", render_csv(&visits)?);
+            return Ok(());
+        }
+        print_json(&visits, "visits")?;
+        return Ok(());
+    }
+
+    if args.mine {
+        if !quiet {
+            outln!("You are checked in for {}:", date_str);
+        }
+        print_roster(&[&visits[0]], format, name_format, args.notes_width);
+        return Ok(());
+    }
+
+    if visits.is_empty() {
+        if !quiet && total > 0 {
+            outln!("No one else is checked in for {}", date_str);
+        } else if !quiet {
+            outln!("No one is checked in for {}", date_str);
+        }
+        return Ok(());
+    }
+
+    if !quiet {
+        outln!("Checked in for {} ({} people):", date_str, visits.len());
+    }
+
+    if let Some(bounds) = bounds {
+        for (period, group) in group_by_period(&visits, bounds, tcurse::hub_tz())? {
+            outln!("{} ({}):", period, group.len());
+            print_roster(&group, format, name_format, args.notes_width);
+        }
+        return Ok(());
+    }
+
+    print_roster(&visits.iter().collect::<Vec<_>>(), format, name_format, args.notes_width);
+
+    Ok(())
+}
+
+fn my_entry(visits: Vec<HubVisit>, my_id: i64, date: &str) -> Result<HubVisit, String> {
+    visits
+        .into_iter()
+        .find(|v| v.person.id == my_id)
+        .ok_or_else(|| format!("You are not checked in for {}", date))
+}
+
+async fn show_checked_in_range(
+    client: &ApiClient,
+    args: &CheckedInArgs,
+    from: &str,
+    output: Option<OutputFormat>,
+    name_format: NameFormat,
+    quiet: bool,
+) -> Result<(), CliError> {
+    let to = args.to.clone().unwrap_or_else(|| get_date_string(None));
+    let (first, last) = (parse_date(from)?, parse_date(&to)?);
+    let format = roster_format(output, args.format)?;
+    let mut visits = filter_by_time(client.get_visits_range(from, &to).await?, args.after, args.before, tcurse::hub_tz())?;
+    if args.mine || args.others {
+        let my_id = client.current_user_id().await?;
+        visits.retain(|v| (v.person.id == my_id) == args.mine);
+    }
+
+    match format {
+        RosterFormat::Json => {
+            print_json(&visits, "visits")?;
+        }
+        RosterFormat::Csv => out!("{}", render_csv(&visits)?),
+        format => {
+            if visits.is_empty() {
+                if !quiet {
+                    outln!("No check-ins from {} to {}", from, to);
+                }
+                return Ok(());
+            }
+            if !quiet {
+                outln!("Checked in from {} to {} ({} check-ins):", from, to, visits.len());
+            }
+            for date in first.iter_days().take_while(|d| *d <= last) {
+                let day: Vec<&HubVisit> = visits.iter().filter(|v| v.parsed_date() == Ok(date)).collect();
+                if !day.is_empty() {
+                    outln!("{} {} ({}):", date.format("%Y-%m-%d"), date.format("%a"), day.len());
+                    print_roster(&day, format, name_format, args.notes_width);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= width {
+        return text;
+    }
+    let mut out: String = text.chars().take(width.saturating_sub(1)).collect();
+    out.push('…');
+    out
+}
+
+fn render_table(visits: &[&HubVisit], name_format: NameFormat, notes_width: usize) -> String {
+    let rows: Vec<(String, String)> = visits
+        .iter()
+        .map(|v| (format_name(&v.person.name, name_format), truncate(v.notes.as_deref().unwrap_or(""), notes_width)))
+        .collect();
+    let name_width = rows.iter().map(|r| r.0.chars().count()).max().unwrap_or(0).max(4);
+
+    let mut out = format!("{:<w$}  NOTES\n", "NAME", w = name_width);
+    for (name, notes) in &rows {
+        if notes.is_empty() {
+            out.push_str(&paint(name, BOLD));
+        } else {
+            out.push_str(&format!("{}  {}", paint(&format!("{:<w$}", name, w = name_width), BOLD), paint(notes, DIM)));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn render_csv(visits: &[HubVisit]) -> Result<String, String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let fail = |e: csv::Error| format!("Failed to write CSV: {}", e);
+    writer.write_record(["date", "person_id", "name", "notes"]).map_err(fail)?;
+    for visit in visits {
+        let id = visit.person.id.to_string();
+        writer
+            .write_record([visit.date.as_str(), &id, &visit.person.name, visit.notes.as_deref().unwrap_or("")])
+            .map_err(fail)?;
+    }
+    let bytes = writer.into_inner().map_err(|e| format!("Failed to write CSV: {}", e))?;
+    String::from_utf8(bytes).map_err(|e| format!("Failed to write CSV: {}", e))
+}
+
+fn print_json<T: Serialize + ?Sized>(value: &T, what: &str) -> Result<(), String> {
+    let out = serde_json::to_string_pretty(value).map_err(|e| format!("Failed to serialize {}: {}", what, e))?;
+    outln!("{}", out);
+    Ok(())
+}
+
+fn print_roster(visits: &[&HubVisit], format: RosterFormat, name_format: NameFormat, notes_width: usize) {
+    match format {
+        RosterFormat::Table => out!("{}", render_table(visits, name_format, notes_width)),
+        _ => {
+            for visit in visits {
+                print_visit(visit, name_format);
+            }
+        }
+    }
+}
+
+fn render_calendar(
+    first: NaiveDate,
+    last: NaiveDate,
+    days: &BTreeMap<NaiveDate, (bool, usize)>,
+    today: NaiveDate,
+    ascii: bool,
+    color: bool,
+) -> String {
+    let (yes, no) = if ascii { ("+", "-") } else { ("✓", "✗") };
+    let mut out = format!("{:^49}\n", first.format("%B %Y").to_string());
+    for name in ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"] {
+        out.push_str(&format!(" {:<6}", name));
+    }
+    out.push('\n');
+
+    let mut cells: Vec<Option<NaiveDate>> = vec![None; first.weekday().num_days_from_sunday() as usize];
+    cells.extend(first.iter_days().take_while(|d| *d <= last).map(Some));
+    while !cells.len().is_multiple_of(7) {
+        cells.push(None);
+    }
+
+    for week in cells.chunks(7) {
+        let mut day_line = String::new();
+        let mut status_line = String::new();
+        for cell in week {
+            match cell {
+                Some(d) => {
+                    let label = if *d == today && color {
+                        format!(" \x1b[7m{:>2}\x1b[0m    ", d.day())
+                    } else if *d == today {
+                        format!("[{:>2}]   ", d.day())
+                    } else {
+                        format!(" {:>2}    ", d.day())
+                    };
+                    day_line.push_str(&label);
+                    let status = match days.get(d) {
+                        Some((mine, count)) => format!("{} {}", if *mine { yes } else { no }, count),
+                        None => String::new(),
+                    };
+                    status_line.push_str(&format!(" {:<6}", status));
+                }
+                None => {
+                    day_line.push_str("       ");
+                    status_line.push_str("       ");
+                }
+            }
+        }
+        out.push_str(day_line.trim_end());
+        out.push('\n');
+        out.push_str(status_line.trim_end());
+        out.push('\n');
+    }
+
+    out.push_str(&format!("\n{} checked in  {} not checked in  (number = people in the hub)\n", yes, no));
+    out
+}
+
+async fn fetch_rosters(client: &ApiClient, start: NaiveDate, end: NaiveDate) -> Result<Vec<(NaiveDate, Vec<HubVisit>)>, ApiError> {
+    let mut rosters: BTreeMap<NaiveDate, Vec<HubVisit>> =
+        start.iter_days().take_while(|d| *d <= end).map(|d| (d, Vec::new())).collect();
+    let visits = client
+        .get_visits_range(&start.format("%Y-%m-%d").to_string(), &end.format("%Y-%m-%d").to_string())
+        .await?;
+    for visit in visits {
+        if let Some(day) = visit.parsed_date().ok().and_then(|date| rosters.get_mut(&date)) {
+            day.push(visit);
+        }
+    }
+    Ok(rosters.into_iter().collect())
+}
+
+async fn whoami(client: &ApiClient, json: bool) -> Result<(), CliError> {
+    let profile = client.get_current_user().await?;
+    if json {
+        print_json(&profile, "profile")?;
+        return Ok(());
+    }
+    outln!("{} (id {})", profile.name, profile.id);
+    Ok(())
+}
+
+async fn is_in(client: &ApiClient, name: &str, quiet: bool, json: bool) -> Result<(), CliError> {
+    let date = get_date_string(None);
+    let visits = client.get_visits(&date).await?;
+    let needle = name.to_lowercase();
+    let matches: Vec<&VisitPerson> = visits
+        .unique_people()
+        .into_iter()
+        .filter(|p| p.name.to_lowercase().contains(&needle))
+        .collect();
+
+    if matches.is_empty() {
+        return Err(format!("No one matching '{}' is checked in for {}", name, date).into());
+    }
+    if json && !quiet {
+        return Ok(print_json(&matches, "people")?);
+    }
+    if !quiet {
+        for person in matches {
+            outln!("{}", person.name);
+        }
+    }
+    Ok(())
+}
+
+async fn find(client: &ApiClient, query: &str, json: bool) -> Result<(), CliError> {
+    let profiles = client.search_profiles(query).await?;
+    if json {
+        return Ok(print_json(&profiles, "profiles")?);
+    }
+    if profiles.is_empty() {
+        outln!("No matches for '{}'", query);
+        return Ok(());
+    }
+
+    for profile in &profiles {
+        let batches = batch_labels(profile);
+        if batches.is_empty() {
+            outln!("{}  {}", profile.id, profile.name);
+        } else {
+            outln!("{}  {} ({})", profile.id, profile.name, batches.join(", "));
+        }
+    }
+    Ok(())
+}
+
+fn batch_labels(profile: &Profile) -> Vec<&str> {
+    profile
+        .stints
+        .iter()
+        .filter_map(|s| s.batch.as_ref())
+        .map(|b| b.short_name.as_deref().unwrap_or(&b.name))
+        .collect()
+}
+
+fn pick_profile(query: &str, mut matches: Vec<Profile>) -> Result<Profile, String> {
+    let exact: Vec<usize> = matches
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.name.trim().eq_ignore_ascii_case(query.trim()))
+        .map(|(i, _)| i)
+        .collect();
+    if let [index] = exact[..] {
+        return Ok(matches.swap_remove(index));
+    }
+    match matches.len() {
+        0 => Err(format!("No matches for '{}'", query)),
+        1 => Ok(matches.remove(0)),
+        n => {
+            let names: Vec<String> = matches.iter().take(10).map(|p| format!("{} ({})", p.name, p.id)).collect();
+            Err(format!("{} people match '{}': {}. Pass a person id to pick one", n, query, names.join(", ")))
+        }
+    }
+}
+
+async fn who(client: &ApiClient, person: &str, json: bool) -> Result<(), CliError> {
+    let profile = if person.trim().parse::<i64>().is_ok() || person.contains('@') {
+        client.get_profile(person).await?
+    } else {
+        pick_profile(person, client.search_profiles(person).await?)?
+    };
+
+    if json {
+        print_json(&profile, "profile")?;
+        return Ok(());
+    }
+
+    outln!("{} (id {})", profile.name, profile.id);
+    let details = [
+        ("Pronouns", profile.pronouns.clone()),
+        ("Email", profile.email.clone()),
+        ("GitHub", profile.github.clone()),
+        ("Zulip id", profile.zulip_id.map(|id| id.to_string())),
+        ("Image", profile.image_path.clone()),
+    ];
+    for (label, value) in details {
+        if let Some(value) = value.filter(|v| !v.is_empty()) {
+            outln!("  {}: {}", label, value);
+        }
+    }
+    let batches = batch_labels(&profile);
+    if !batches.is_empty() {
+        outln!("  Batches: {}", batches.join(", "));
+    }
+    Ok(())
+}
+
+async fn batches(client: &ApiClient, all: bool, json: bool) -> Result<(), CliError> {
+    let today = tcurse::hub_today();
+    let mut batches: Vec<Batch> = client
+        .get_batches()
+        .await?
+        .into_iter()
+        .filter(|b| all || b.dates().is_none_or(|(_, end)| end >= today))
+        .collect();
+    batches.sort_by_key(|b| b.dates().map(|(start, _)| start));
+
+    if json {
+        print_json(&batches, "batches")?;
+        return Ok(());
+    }
+
+    if batches.is_empty() {
+        outln!("No current or upcoming batches");
+        return Ok(());
+    }
+
+    for batch in &batches {
+        let dates = match batch.dates() {
+            Some((start, end)) => format!("{} to {}", start, end),
+            None => "dates unknown".to_string(),
+        };
+        let label = batch.short_name.as_deref().map_or(batch.name.clone(), |short| format!("{} ({})", batch.name, short));
+        if batch.in_session(today) {
+            outln!("* {}  {}  {}", paint(&label, BOLD), dates, paint("in session", DIM));
+        } else {
+            outln!("  {}  {}", label, dates);
+        }
+    }
+    Ok(())
+}
+
+fn tagged(visits: Vec<HubVisit>, tag: Option<&str>) -> Vec<HubVisit> {
+    let tag = match tag {
+        Some(tag) => tag.trim_start_matches('#').to_lowercase(),
+        None => return visits,
+    };
+    visits
+        .into_iter()
+        .filter(|visit| tcurse::extract_hashtags(visit.notes.as_deref().unwrap_or_default()).contains(&tag))
+        .collect()
+}
+
+fn first_or_last<T>(mut items: Vec<T>, first: Option<usize>, last: Option<usize>) -> Vec<T> {
+    if let Some(n) = first {
+        items.truncate(n);
+    }
+    if let Some(n) = last {
+        items.drain(..items.len().saturating_sub(n));
+    }
+    items
+}
+
+async fn history(client: &ApiClient, args: HistoryArgs, json: bool) -> Result<(), CliError> {
+    let HistoryArgs { start, end, person, tag, first, last, .. } = args;
+    parse_date(&start)?;
+    parse_date(&end)?;
+    let person_id = match person {
+        Some(id) => id,
+        None => client.current_user_id().await?,
+    };
+    let visits = tagged(client.get_person_visits(person_id, &start, &end).await?, tag.as_deref());
+    let present = visits.dates_present(person_id);
+    let total = present.len();
+    let dates = first_or_last(present, first, last);
+    if json {
+        return Ok(print_json(&dates, "dates")?);
+    }
+
+    outln!("Present on {} days between {} and {}", total, start, end);
+    if dates.len() < total {
+        outln!("Showing the {} {}:", if first.is_some() { "first" } else { "last" }, dates.len());
+    }
+    for date in &dates {
+        outln!("  {} {}", date.format("%Y-%m-%d"), date.format("%a"));
+    }
+    Ok(())
+}
+
+async fn range(client: &ApiClient, start: &str, end: &str, output: Option<OutputFormat>) -> Result<(), CliError> {
+    let (first, last) = (parse_date(start)?, parse_date(end)?);
+    let visits = client.get_visits_range(start, end).await?;
+
+    if output == Some(OutputFormat::Csv) {
+        out!("{}", render_csv(&visits)?);
+        return Ok(());
+    }
+
+    let mut counts: BTreeMap<NaiveDate, usize> = first.iter_days().take_while(|d| *d <= last).map(|d| (d, 0)).collect();
+    for visit in &visits {
+        if let Ok(date) = visit.parsed_date() {
+            *counts.entry(date).or_default() += 1;
+        }
+    }
+
+    if output == Some(OutputFormat::Json) {
+        return Ok(print_json(&counts, "counts")?);
+    }
+
+    outln!("Hub attendance from {} to {}:", start, end);
+    for (date, count) in &counts {
+        outln!("  {} {}  {}", date.format("%Y-%m-%d"), date.format("%a"), count);
+    }
+    outln!("{} check-ins over {} days", visits.len(), counts.len());
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Summary {
+    days: usize,
+    check_ins: usize,
+    unique_attendees: usize,
+    busiest_day: Option<NaiveDate>,
+    busiest_day_count: usize,
+    average_per_day: f64,
+    your_days: usize,
+}
+
+fn summarize(visits_by_day: &[(NaiveDate, Vec<HubVisit>)], my_id: i64) -> Summary {
+    let days = visits_by_day.len();
+    let check_ins: usize = visits_by_day.iter().map(|(_, visits)| visits.len()).sum();
+    let attendees: HashSet<i64> = visits_by_day.iter().flat_map(|(_, visits)| visits.iter().map(|v| v.person.id)).collect();
+    let busiest = visits_by_day
+        .iter()
+        .filter(|(_, visits)| !visits.is_empty())
+        .max_by(|(a_date, a), (b_date, b)| a.len().cmp(&b.len()).then(b_date.cmp(a_date)));
+
+    Summary {
+        days,
+        check_ins,
+        unique_attendees: attendees.len(),
+        busiest_day: busiest.map(|(date, _)| *date),
+        busiest_day_count: busiest.map_or(0, |(_, visits)| visits.len()),
+        average_per_day: if days == 0 { 0.0 } else { check_ins as f64 / days as f64 },
+        your_days: visits_by_day.iter().filter(|(_, visits)| visits.iter().any(|v| v.person.id == my_id)).count(),
+    }
+}
+
+async fn summary(client: &ApiClient, start: &str, end: &str, json: bool) -> Result<(), CliError> {
+    let (first, last) = (parse_date(start)?, parse_date(end)?);
+    let my_id = client.current_user_id().await?;
+    let summary = summarize(&fetch_rosters(client, first, last).await?, my_id);
+
+    if json {
+        print_json(&summary, "summary")?;
+        return Ok(());
+    }
+
+    outln!("Hub attendance from {} to {}:", start, end);
+    outln!("  Days:              {}", summary.days);
+    outln!("  Check-ins:         {}", summary.check_ins);
+    outln!("  Unique attendees:  {}", summary.unique_attendees);
+    match summary.busiest_day {
+        Some(date) => outln!("  Busiest day:       {} {} ({})", date.format("%Y-%m-%d"), date.format("%a"), summary.busiest_day_count),
+        None => outln!("  Busiest day:       -"),
+    }
+    outln!("  Average per day:   {:.1}", summary.average_per_day);
+    outln!("  Your days:         {} of {}", summary.your_days, summary.days);
+    Ok(())
+}
+
+fn calendar_month(month: Option<&str>, today: NaiveDate) -> Result<(NaiveDate, NaiveDate), String> {
+    let month = month.map_or_else(|| today.format("%Y-%m").to_string(), str::to_string);
+    let (first, last) = match tcurse::parse_date_spec(&month) {
+        Ok(spec @ tcurse::DateSpec::Month { .. }) => spec.range(),
+        _ => return Err("Invalid month format. Use YYYY-MM".to_string()),
+    };
+    if first > today {
+        return Err(format!("{} is in the future; the latest month with check-ins is {}", month, today.format("%Y-%m")));
+    }
+    Ok((first, last))
+}
+
+async fn calendar(client: &ApiClient, month: Option<String>, ascii: bool, json: bool) -> Result<(), CliError> {
+    let today = tcurse::hub_today();
+    let (first, last) = calendar_month(month.as_deref(), today)?;
+    let my_id = client.current_user_id().await?;
+
+    let days: BTreeMap<NaiveDate, (bool, usize)> = fetch_rosters(client, first, last.min(today))
+        .await?
+        .into_iter()
+        .map(|(date, visits)| (date, (visits.iter().any(|v| v.person.id == my_id), visits.len())))
+        .collect();
+
+    if json {
+        let days: Vec<serde_json::Value> = days
+            .iter()
+            .map(|(date, (mine, count))| serde_json::json!({ "date": date, "checked_in": mine, "people": count }))
+            .collect();
+        return Ok(print_json(&days, "calendar")?);
+    }
+
+    let color = !ascii && use_color();
+    out!("{}", render_calendar(first, last, &days, today, ascii, color));
+
+    Ok(())
+}
+
+async fn are_they_in(
+    client: &ApiClient,
+    people: Vec<String>,
+    date: Option<String>,
+    name_format: NameFormat,
+    json: bool,
+) -> Result<(), CliError> {
+    let date_str = get_date_string(date);
+
+    parse_date(&date_str)?;
+
+    let mut resolved: Vec<(&str, i64, Option<String>)> = Vec::new();
+    let mut unresolved = 0;
+    for query in &people {
+        let query = query.trim();
+        if let Ok(id) = query.parse::<i64>() {
+            resolved.push((query, id, None));
+            continue;
+        }
+        match pick_profile(query, client.search_profiles(query).await?) {
+            Ok(profile) => resolved.push((query, profile.id, Some(profile.name))),
+            Err(e) => {
+                unresolved += 1;
+                eprintln!("{}", e);
+            }
+        }
+    }
+
+    let ids: Vec<i64> = resolved.iter().map(|(_, id, _)| *id).collect();
+    let present = client.who_is_in(&ids, &date_str).await?;
+    if json {
+        let statuses: Vec<serde_json::Value> = resolved
+            .iter()
+            .map(|(query, id, name)| {
+                serde_json::json!({ "query": query, "person_id": id, "name": name, "date": date_str, "checked_in": present[id] })
+            })
+            .collect();
+        print_json(&statuses, "people")?;
+    } else {
+        for (query, id, name) in &resolved {
+            let status = if present[id] { "in" } else { "not in" };
+            match name {
+                Some(name) => outln!("{}: {} ({})", query, status, format_name(name, name_format)),
+                None => outln!("{}: {}", query, status),
+            }
+        }
+    }
+
+    if unresolved > 0 {
+        return Err(format!("Could not resolve {} of {} people", unresolved, people.len()).into());
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+struct Window {
+    start: NaiveDate,
+    end: NaiveDate,
+    label: String,
+}
+
+fn resolve_window(days: u32, range: &RangeArgs) -> Result<Window, String> {
+    let today = tcurse::hub_today();
+    if let Some(since) = &range.since {
+        let start = today
+            .checked_sub_signed(tcurse::parse_duration_spec(since)? - Duration::days(1))
+            .ok_or_else(|| format!("--since {} reaches further back than any supported date", since))?;
+        return Ok(Window {
+            start,
+            end: today,
+            label: format!("the last {}", since),
+        });
+    }
+    let during = match &range.during {
+        Some(during) => during,
+        None => {
+            let start = today
+                .checked_sub_signed(Duration::days(days.saturating_sub(1) as i64))
+                .ok_or_else(|| format!("--days {} reaches further back than any supported date", days))?;
+            return Ok(Window {
+                start,
+                end: today,
+                label: format!("the last {} days", days),
+            })
+        }
+    };
+    let (start, end) = tcurse::parse_date_spec(during)?.range();
+    if start > today {
+        return Err(format!("{} is in the future", during));
+    }
+    Ok(Window {
+        start,
+        end: end.min(today),
+        label: during.to_string(),
+    })
+}
+
+async fn tags(client: &ApiClient, window: Window, json: bool) -> Result<(), CliError> {
+    let my_id = client.current_user_id().await?;
+    let visits = client.get_person_visits(my_id, &window.start.to_string(), &window.end.to_string()).await?;
+
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for visit in visits.with_notes() {
+        for tag in tcurse::extract_hashtags(visit.notes.as_deref().unwrap_or_default()) {
+            *counts.entry(tag).or_default() += 1;
+        }
+    }
+
+    if json {
+        print_json(&counts, "tags")?;
+        return Ok(());
+    }
+
+    if counts.is_empty() {
+        outln!("No tags in your notes for {}", window.label);
+        return Ok(());
+    }
+
+    let mut sorted: Vec<(&String, &usize)> = counts.iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    outln!("Tags in your notes for {}:", window.label);
+    for (tag, count) in sorted {
+        outln!("  #{} {}", tag, count);
+    }
+
+    Ok(())
+}
+
+fn render_digest(date: &str, visits: &[HubVisit], format: DigestFormat, name_format: NameFormat) -> String {
+    let names: Vec<String> = visits.iter().map(|v| format_name(&v.person.name, name_format)).collect();
+    let noted: Vec<(String, &str)> = visits
+        .with_notes()
+        .into_iter()
+        .map(|v| (format_name(&v.person.name, name_format), v.notes.as_deref().unwrap_or_default().trim()))
+        .collect();
+
+    let mut out = String::new();
+    match format {
+        DigestFormat::Plain => {
+            out.push_str(&format!("Hub digest for {}\n", date));
+            out.push_str(&format!("{} people checked in\n", visits.len()));
+            if !visits.is_empty() {
+                out.push_str("\nPeople:\n");
+                for name in &names {
+                    out.push_str(&format!("  - {}\n", name));
+                }
+            }
+            if !noted.is_empty() {
+                out.push_str("\nNotes:\n");
+                for (name, notes) in &noted {
+                    out.push_str(&format!("  - {}: {}\n", name, notes));
+                }
+            }
+        }
+        DigestFormat::Markdown => {
+            out.push_str(&format!("# Hub digest for {}\n\n", date));
+            out.push_str(&format!("**{} people checked in**\n", visits.len()));
+            if !visits.is_empty() {
+                out.push_str("\n## People\n\n");
+                for name in &names {
+                    out.push_str(&format!("- {}\n", name));
+                }
+            }
+            if !noted.is_empty() {
+                out.push_str("\n## Notes\n\n");
+                for (name, notes) in &noted {
+                    out.push_str(&format!("- **{}**: {}\n", name, notes));
+                }
+            }
+        }
+    }
+    out
+}
+
+async fn digest(client: &ApiClient, date: Option<String>, format: DigestFormat, name_format: NameFormat, json: bool) -> Result<(), CliError> {
+    let date_str = get_date_string(date);
+
+    parse_date(&date_str)?;
+
+    let visits = client.get_visits(&date_str).await?;
+    if json {
+        let digest = serde_json::json!({ "date": date_str, "people": visits.len(), "visits": visits });
+        return Ok(print_json(&digest, "digest")?);
+    }
+    out!("{}", render_digest(&date_str, &visits, format, name_format));
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Gap {
+    start: NaiveDate,
+    end: NaiveDate,
+    days: i64,
+}
+
+fn find_gaps(calendar: &BTreeMap<NaiveDate, bool>) -> Vec<Gap> {
+    let mut gaps = Vec::new();
+    let mut last_present: Option<NaiveDate> = None;
+    for (date, present) in calendar {
+        if !present {
+            continue;
+        }
+        if let Some(prev) = last_present {
+            let days = (*date - prev).num_days() - 1;
+            if days > 0 {
+                gaps.push(Gap {
+                    start: prev + Duration::days(1),
+                    end: *date - Duration::days(1),
+                    days,
+                });
+            }
+        }
+        last_present = Some(*date);
+    }
+    gaps
+}
+
+async fn streak(client: &ApiClient, window: Window, skip_weekends: bool, json: bool) -> Result<(), CliError> {
+    let my_id = client.current_user_id().await?;
+    let dates: Vec<NaiveDate> = client
+        .attendance_calendar(my_id, window.start, window.end)
+        .await?
+        .into_iter()
+        .filter(|(_, present)| *present)
+        .map(|(date, _)| date)
+        .collect();
+    let (current, longest) = tcurse::compute_streaks(&dates, window.end, skip_weekends);
+    if json {
+        return Ok(print_json(&serde_json::json!({ "current": current, "longest": longest }), "streaks")?);
+    }
+
+    outln!("Streaks over {}{}:", window.label, if skip_weekends { ", ignoring weekends" } else { "" });
+    outln!("  Current: {} {}", current, if current == 1 { "day" } else { "days" });
+    outln!("  Longest: {} {}", longest, if longest == 1 { "day" } else { "days" });
+    Ok(())
+}
+
+async fn gaps(client: &ApiClient, window: Window, min: i64, json: bool) -> Result<(), CliError> {
+    let my_id = client.current_user_id().await?;
+    let calendar = client.attendance_calendar(my_id, window.start, window.end).await?;
+    let gaps: Vec<Gap> = find_gaps(&calendar).into_iter().filter(|g| g.days >= min).collect();
+
+    if json {
+        print_json(&gaps, "gaps")?;
+        return Ok(());
+    }
+
+    if gaps.is_empty() {
+        outln!("No gaps between your check-ins in {}", window.label);
+        return Ok(());
+    }
+
+    outln!("Gaps between your check-ins in {}:", window.label);
+    for gap in gaps {
+        let plural = if gap.days == 1 { "day" } else { "days" };
+        outln!("  - {} to {} ({} {})", gap.start, gap.end, gap.days, plural);
+    }
+
+    Ok(())
+}
+
+fn render_metrics(date: &str, visits: &[HubVisit], my_id: i64, people: &[i64]) -> String {
+    let mut out = String::new();
+    out.push_str("# HELP tcurse_checked_in_total Number of people checked in to the hub.\n");
+    out.push_str("# TYPE tcurse_checked_in_total gauge\n");
+    out.push_str(&format!("tcurse_checked_in_total{{date=\"{}\"}} {}\n", date, visits.len()));
+
+    let mine = visits.iter().any(|v| v.person.id == my_id);
+    out.push_str("# HELP tcurse_my_checked_in Whether you are checked in to the hub.\n");
+    out.push_str("# TYPE tcurse_my_checked_in gauge\n");
+    out.push_str(&format!("tcurse_my_checked_in{{date=\"{}\"}} {}\n", date, mine as u8));
+
+    if !people.is_empty() {
+        let present = tcurse::presence(visits, people);
+        out.push_str("# HELP tcurse_person_checked_in Whether a person is checked in to the hub.\n");
+        out.push_str("# TYPE tcurse_person_checked_in gauge\n");
+        for id in people {
+            out.push_str(&format!(
+                "tcurse_person_checked_in{{date=\"{}\",person_id=\"{}\"}} {}\n",
+                date, id, present[id] as u8
+            ));
+        }
+    }
+    out
+}
+
+async fn metrics(client: &ApiClient, date: Option<String>, people: Vec<i64>) -> Result<(), CliError> {
+    let date_str = get_date_string(date);
+
+    parse_date(&date_str)?;
+
+    let my_id = client.current_user_id().await?;
+    let visits = client.get_visits(&date_str).await?;
+    out!("{}", render_metrics(&date_str, &visits, my_id, &people));
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct PresenceEvent {
+    event: &'static str,
+    date: String,
+    person_id: i64,
+    name: String,
+    at: String,
+}
+
+fn presence_events(date: &str, previous: &[VisitPerson], current: &[VisitPerson], at: &str) -> Vec<PresenceEvent> {
+    let event = |kind, person: &VisitPerson| PresenceEvent {
+        event: kind,
+        date: date.to_string(),
+        person_id: person.id,
+        name: person.name.clone(),
+        at: at.to_string(),
+    };
+    let arrivals = current
+        .iter()
+        .filter(|p| !previous.iter().any(|q| q.id == p.id))
+        .map(|p| event("arrival", p));
+    let departures = previous
+        .iter()
+        .filter(|p| !current.iter().any(|q| q.id == p.id))
+        .map(|p| event("departure", p));
+    arrivals.chain(departures).collect()
+}
+
+async fn emit_event(http: &reqwest::Client, sink: Sink, webhook_url: Option<&str>, event: &PresenceEvent) -> Result<(), String> {
+    match sink {
+        Sink::Stdout => {
+            let line = serde_json::to_string(event).map_err(|e| format!("Failed to serialize event: {}", e))?;
+            outln!("{}", line);
+        }
+        Sink::Webhook => {
+            let url = webhook_url.ok_or_else(|| "--webhook-url is required for the webhook sink".to_string())?;
+            let response = http
+                .post(url)
+                .json(event)
+                .send()
+                .await
+                .map_err(|e| format!("Webhook request failed: {}", e))?;
+            if !response.status().is_success() {
+                return Err(format!("Webhook error: {}", response.status()));
+            }
+        }
+        Sink::Desktop => {
+            let verb = if event.event == "arrival" { "arrived at" } else { "left" };
+            let message = format!("{} {} the hub", event.name, verb);
+            let status = if cfg!(target_os = "macos") {
+                std::process::Command::new("osascript")
+                    .arg("-e")
+                    .arg(format!("display notification {:?} with title \"tcurse\"", message))
+                    .status()
+            } else {
+                std::process::Command::new("notify-send").arg("tcurse").arg(&message).status()
+            };
+            match status {
+                Ok(s) if s.success() => {}
+                Ok(s) => return Err(format!("Desktop notification failed ({})", s)),
+                Err(e) => return Err(format!("Desktop notification failed: {}", e)),
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(s) => s,
+            Err(_) => {
+                tokio::signal::ctrl_c().await.ok();
+                return;
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await.ok();
+}
+
+async fn daemon(client: &ApiClient, interval: u64, sink: Sink, webhook_url: Option<String>, people: Vec<i64>) -> Result<(), CliError> {
+    let http = reqwest::Client::builder()
+        .user_agent(concat!("tcurse/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs(interval));
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    let mut state: Option<(String, Vec<VisitPerson>)> = None;
+    let mut succeeded = false;
+
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = ticker.tick() => {}
+        }
+
+        let date = tcurse::hub_today().format("%Y-%m-%d").to_string();
+        let visits = match client.get_visits(&date).await {
+            Ok(v) => v,
+            Err(e) => {
+                let e = CliError::from(e);
+                if let Some(revoked) = revoked_token(&e, succeeded) {
+                    return Err(revoked);
+                }
+                eprintln!("Poll failed: {}", e);
+                continue;
+            }
+        };
+        succeeded = true;
+        let current: Vec<VisitPerson> = visits
+            .unique_people()
+            .into_iter()
+            .filter(|p| people.is_empty() || people.contains(&p.id))
+            .cloned()
+            .collect();
+
+        if let Some((previous_date, previous)) = &state {
+            if *previous_date == date {
+                let at = tcurse::hub_now().to_rfc3339();
+                for event in presence_events(&date, previous, &current, &at) {
+                    if let Err(e) = emit_event(&http, sink, webhook_url.as_deref(), &event).await {
+                        eprintln!("{}", e);
+                    }
+                }
+            }
+        }
+        state = Some((date, current));
+    }
+
+    match state {
+        Some((date, current)) => eprintln!("Stopped. {} people checked in for {}", current.len(), date),
+        None => eprintln!("Stopped before the first poll completed"),
+    }
+    Ok(())
+}
+
+struct Dashboard {
+    date: NaiveDate,
+    my_id: i64,
+    visits: Vec<HubVisit>,
+    updated: String,
+    status: String,
+    draft: Option<String>,
+}
+
+async fn tui(client: &ApiClient, interval: u64, name_format: NameFormat, allow_secrets: bool, dry_run: bool) -> Result<(), CliError> {
+    if OUTPUT_FILE.get().is_some() || !std::io::stdout().is_terminal() || !std::io::stdin().is_terminal() {
+        return Err("The dashboard needs an interactive terminal".to_string().into());
+    }
+    let my_id = client.current_user_id().await?;
+
+    let mut terminal = ratatui::init();
+    let result = run_dashboard(&mut terminal, client, my_id, interval, name_format, allow_secrets, dry_run).await;
+    ratatui::restore();
+    result
+}
+
+async fn run_dashboard(
+    terminal: &mut ratatui::DefaultTerminal,
+    client: &ApiClient,
+    my_id: i64,
+    interval: u64,
+    name_format: NameFormat,
+    allow_secrets: bool,
+    dry_run: bool,
+) -> Result<(), CliError> {
+    use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+
+    let terminal_error = |e: std::io::Error| format!("Terminal error: {}", e);
+    let refresh_every = std::time::Duration::from_secs(interval);
+    let mut board = Dashboard {
+        date: tcurse::hub_today(),
+        my_id,
+        visits: Vec::new(),
+        updated: "never".to_string(),
+        status: String::new(),
+        draft: None,
+    };
+    let mut refreshed: Option<std::time::Instant> = None;
+
+    loop {
+        let today = tcurse::hub_today();
+        if refreshed.is_none_or(|at| at.elapsed() >= refresh_every) {
+            board.updated = "loading…".to_string();
+            terminal.draw(|frame| render_dashboard(frame, &board, name_format)).map_err(terminal_error)?;
+            match client.get_visits(&board.date.format("%Y-%m-%d").to_string()).await {
+                Ok(visits) => board.visits = visits,
+                Err(e) => board.status = format!("Refresh failed: {}", e),
+            }
+            board.updated = tcurse::hub_now().format("%H:%M:%S").to_string();
+            refreshed = Some(std::time::Instant::now());
+        }
+        terminal.draw(|frame| render_dashboard(frame, &board, name_format)).map_err(terminal_error)?;
+
+        if !event::poll(std::time::Duration::from_millis(250)).map_err(terminal_error)? {
+            continue;
+        }
+        let Event::Key(key) = event::read().map_err(terminal_error)? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if let Some(draft) = board.draft.as_mut() {
+            match key.code {
+                KeyCode::Esc => board.draft = None,
+                KeyCode::Enter => {
+                    let notes = board.draft.take().unwrap_or_default();
+                    board.status = save_notes(client, &board, &notes, allow_secrets, dry_run).await;
+                    refreshed = None;
+                }
+                KeyCode::Backspace => {
+                    draft.pop();
+                }
+                KeyCode::Char(c) => draft.push(c),
+                _ => {}
+            }
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('r') => refreshed = None,
+            KeyCode::Left | KeyCode::Char('h') => {
+                board.date = board.date.pred_opt().unwrap_or(board.date);
+                refreshed = None;
+            }
+            KeyCode::Right | KeyCode::Char('l') if board.date < today => {
+                board.date = board.date.succ_opt().unwrap_or(board.date);
+                refreshed = None;
+            }
+            KeyCode::Char('t') => {
+                board.date = today;
+                refreshed = None;
+            }
+            KeyCode::Char('c') => {
+                board.status = toggle_checkin(client, &board, dry_run).await;
+                refreshed = None;
+            }
+            KeyCode::Char('n') => {
+                let mine = board.visits.iter().find(|v| v.person.id == board.my_id);
+                board.draft = Some(mine.and_then(|v| v.notes.clone()).unwrap_or_default());
+            }
+            _ => {}
+        }
+    }
+}
+
+async fn toggle_checkin(client: &ApiClient, board: &Dashboard, dry_run: bool) -> String {
+    let date = board.date.format("%Y-%m-%d").to_string();
+    let checked_in = board.visits.iter().any(|v| v.person.id == board.my_id);
+    match (checked_in, dry_run) {
+        (true, true) => format!("[dry-run] Would remove your check-in for {}", date),
+        (false, true) => format!("[dry-run] Would check you in for {}", date),
+        (true, false) => match client.delete_visit(board.my_id, &date).await {
+            Ok(()) => format!("Removed check-in for {}", date),
+            Err(e) => format!("Failed to remove check-in: {}", e),
+        },
+        (false, false) => match client.create_or_update_visit(board.my_id, &date, None).await {
+            Ok(_) => format!("Checked in for {}", date),
+            Err(e) => format!("Failed to check in: {}", e),
+        },
+    }
+}
+
+async fn save_notes(client: &ApiClient, board: &Dashboard, notes: &str, allow_secrets: bool, dry_run: bool) -> String {
+    let date = board.date.format("%Y-%m-%d").to_string();
+    if let Err(e) = check_secrets(Some(notes), allow_secrets) {
+        return e;
+    }
+    if dry_run {
+        return format!("[dry-run] Would check you in for {} with notes: {}", date, notes);
+    }
+    match client.create_or_update_visit(board.my_id, &date, Some(notes)).await {
+        Ok(_) => format!("Saved your notes for {}", date),
+        Err(e) => format!("Failed to save notes: {}", e),
+    }
+}
+
+fn render_dashboard(frame: &mut ratatui::Frame, board: &Dashboard, name_format: NameFormat) {
+    use ratatui::layout::{Constraint, Layout};
+    use ratatui::style::{Modifier, Style};
+    use ratatui::text::{Line, Span};
+    use ratatui::widgets::{Block, List, ListItem, Paragraph};
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let dim = Style::default().add_modifier(Modifier::DIM);
+    let [header, body, footer] =
+        Layout::vertical([Constraint::Length(2), Constraint::Min(3), Constraint::Length(2)]).areas(frame.area());
+
+    let me = if board.visits.iter().any(|v| v.person.id == board.my_id) {
+        "You're checked in"
+    } else {
+        "You're not checked in"
+    };
+    frame.render_widget(
+        Paragraph::new(vec![
+            Line::from(vec![
+                Span::styled(board.date.format("%a %Y-%m-%d").to_string(), bold),
+                Span::raw(format!("  {} checked in", board.visits.len())),
+            ]),
+            Line::from(format!("{} · updated {}", me, board.updated)),
+        ]),
+        header,
+    );
+
+    let block = Block::bordered().title(" Roster ");
+    if board.visits.is_empty() {
+        frame.render_widget(Paragraph::new("No one is checked in").style(dim).block(block), body);
+    } else {
+        let items: Vec<ListItem> = board
+            .visits
+            .iter()
+            .map(|v| {
+                let mut spans = vec![Span::styled(format_name(&v.person.name, name_format), bold)];
+                if let Some(notes) = v.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+                    spans.push(Span::raw("  "));
+                    spans.push(Span::styled(notes.split_whitespace().collect::<Vec<_>>().join(" "), dim));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        frame.render_widget(List::new(items).block(block), body);
+    }
+
+    let footer_lines = match &board.draft {
+        Some(draft) => vec![Line::from(format!("Notes: {}_", draft)), Line::styled("Enter save  Esc cancel", dim)],
+        None => vec![
+            Line::from(board.status.as_str()),
+            Line::styled("←/→ change day  t today  c toggle your check-in  n edit notes  r refresh  q quit", dim),
+        ],
+    };
+    frame.render_widget(Paragraph::new(footer_lines), footer);
+}
+
+#[derive(Clone, PartialEq)]
+enum Kind {
+    Unknown,
+    Bool,
+    Integer,
+    Float,
+    String,
+    Array(Box<Shape>),
+    Object(BTreeMap<String, (Shape, bool)>),
+    Mixed(Vec<Kind>),
+}
+
+#[derive(Clone, PartialEq)]
+struct Shape {
+    kind: Kind,
+    nullable: bool,
+}
+
+fn infer_shape(value: &serde_json::Value) -> Shape {
+    use serde_json::Value;
+    let kind = match value {
+        Value::Null => return Shape { kind: Kind::Unknown, nullable: true },
+        Value::Bool(_) => Kind::Bool,
+        Value::Number(n) if n.is_f64() => Kind::Float,
+        Value::Number(_) => Kind::Integer,
+        Value::String(_) => Kind::String,
+        Value::Array(items) => {
+            let element = items
+                .iter()
+                .map(infer_shape)
+                .reduce(merge_shapes)
+                .unwrap_or(Shape { kind: Kind::Unknown, nullable: false });
+            Kind::Array(Box::new(element))
+        }
+        Value::Object(map) => Kind::Object(map.iter().map(|(k, v)| (k.clone(), (infer_shape(v), true))).collect()),
+    };
+    Shape { kind, nullable: false }
+}
+
+fn merge_kinds(a: Kind, b: Kind) -> Kind {
+    match (a, b) {
+        (a, b) if a == b => a,
+        (Kind::Unknown, other) | (other, Kind::Unknown) => other,
+        (Kind::Integer, Kind::Float) | (Kind::Float, Kind::Integer) => Kind::Float,
+        (Kind::Array(a), Kind::Array(b)) => Kind::Array(Box::new(merge_shapes(*a, *b))),
+        (Kind::Object(mut a), Kind::Object(mut b)) => {
+            let keys: Vec<String> = a.keys().chain(b.keys()).cloned().collect();
+            let mut merged = BTreeMap::new();
+            for key in keys {
+                if merged.contains_key(&key) {
+                    continue;
+                }
+                let field = match (a.remove(&key), b.remove(&key)) {
+                    (Some((x, rx)), Some((y, ry))) => (merge_shapes(x, y), rx && ry),
+                    (Some((x, _)), None) | (None, Some((x, _))) => (x, false),
+                    (None, None) => unreachable!(),
+                };
+                merged.insert(key, field);
+            }
+            Kind::Object(merged)
+        }
+        (Kind::Mixed(mut kinds), other) | (other, Kind::Mixed(mut kinds)) => {
+            if !kinds.contains(&other) {
+                kinds.push(other);
+            }
+            Kind::Mixed(kinds)
+        }
+        (a, b) => Kind::Mixed(vec![a, b]),
+    }
+}
+
+fn merge_shapes(a: Shape, b: Shape) -> Shape {
+    Shape {
+        nullable: a.nullable || b.nullable,
+        kind: merge_kinds(a.kind, b.kind),
+    }
+}
+
+fn render_kind(kind: &Kind, indent: usize) -> String {
+    match kind {
+        Kind::Unknown => "unknown".to_string(),
+        Kind::Bool => "boolean".to_string(),
+        Kind::Integer => "integer".to_string(),
+        Kind::Float => "number".to_string(),
+        Kind::String => "string".to_string(),
+        Kind::Array(element) => format!("array of {}", render_shape(element, indent)),
+        Kind::Mixed(kinds) => kinds.iter().map(|k| render_kind(k, indent)).collect::<Vec<_>>().join(" | "),
+        Kind::Object(fields) => {
+            let pad = "  ".repeat(indent + 1);
+            let mut out = String::from("{\n");
+            for (name, (shape, required)) in fields {
+                let marker = if *required { "" } else { " (optional)" };
+                out.push_str(&format!("{}{}: {}{}\n", pad, name, render_shape(shape, indent + 1), marker));
+            }
+            out.push_str(&format!("{}}}", "  ".repeat(indent)));
+            out
+        }
+    }
+}
+
+fn render_shape(shape: &Shape, indent: usize) -> String {
+    match (&shape.kind, shape.nullable) {
+        (Kind::Unknown, true) => "null".to_string(),
+        (kind, true) => format!("{} | null", render_kind(kind, indent)),
+        (kind, false) => render_kind(kind, indent),
+    }
+}
+
+async fn inspect(client: &ApiClient, path: &str) -> Result<(), CliError> {
+    let response = client.get_raw(path).await?;
+    if !response.status().is_success() {
+        return Err(tcurse::error_from_response(response).await.into());
+    }
+    let value: serde_json::Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse response: {}", e))?;
+    outln!("{}", render_shape(&infer_shape(&value), 0));
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+struct ImportRow {
+    date: NaiveDate,
+    notes: Option<String>,
+}
+
+fn parse_import_csv(input: &str) -> Vec<(usize, Result<ImportRow, String>)> {
+    let mut rows = Vec::new();
+    let mut csv = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(input.as_bytes());
+    let line_at = |position: Option<&csv::Position>| match position {
+        Some(p) => {
+            let rest = &input[p.byte() as usize..];
+            let start = input.len() - rest.trim_start_matches(['\r', '\n']).len();
+            input[..start].matches('\n').count() + 1
+        }
+        None => 0,
+    };
+
+    for (index, record) in csv.records().enumerate() {
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => {
+                let line = line_at(e.position());
+                rows.push((line, Err(format!("unreadable row ({})", e))));
+                continue;
+            }
+        };
+        let line = line_at(record.position());
+        let date = record.get(0).unwrap_or_default();
+        if index == 0 && date.eq_ignore_ascii_case("date") {
+            continue;
+        }
+        if date.is_empty() {
+            continue;
+        }
+        let parsed = parse_date(date).map(|d| ImportRow {
+            date: d,
+            notes: record.get(1).filter(|n| !n.is_empty()).map(str::to_string),
+        });
+        rows.push((line, parsed));
+    }
+    rows
+}
+
+async fn import(client: &ApiClient, input: &Path, allow_secrets: bool, dry_run: bool) -> Result<(), CliError> {
+    let contents = std::fs::read_to_string(input).map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+    let rows = parse_import_csv(&contents);
+    let my_id = client.current_user_id().await?;
+
+    let (mut created, mut skipped, mut failed) = (0, 0, 0);
+    for (line, row) in rows {
+        let ImportRow { date, notes } = match row.and_then(|r| check_secrets(r.notes.as_deref(), allow_secrets).map(|_| r)) {
+            Ok(r) => r,
+            Err(e) => {
+                failed += 1;
+                println!("  - line {}: failed ({})", line, e);
+                continue;
+            }
+        };
+        let date = date.format("%Y-%m-%d").to_string();
+        let result = match client.get_visit(my_id, &date).await {
+            Ok(Some(_)) => {
+                skipped += 1;
+                println!("  - {}: skipped (already checked in)", date);
+                continue;
+            }
+            Ok(None) if dry_run => {
+                created += 1;
+                println!("  - {}: [dry-run] would create", date);
+                continue;
+            }
+            Ok(None) => client.create_or_update_visit(my_id, &date, notes.as_deref()).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(_) => {
+                created += 1;
+                println!("  - {}: created", date);
+            }
+            Err(e) => {
+                failed += 1;
+                println!("  - {}: failed ({})", date, e);
+            }
+        }
+    }
+
+    let prefix = if dry_run { "[dry-run] Would have imported" } else { "Imported" };
+    println!("{} {} created, {} skipped, {} failed", prefix, created, skipped, failed);
+    if failed > 0 {
+        return Err(format!("{} rows failed to import", failed).into());
+    }
+
+    Ok(())
+}
+
+fn parse_backfill_line(line: &str) -> Option<Result<ImportRow, String>> {
+    let line = line.trim_end_matches(['\r', '\n']);
+    if line.trim().is_empty() || line.trim_start().starts_with('#') {
+        return None;
+    }
+    let (date, notes) = match line.split_once('\t') {
+        Some((date, notes)) => (date, Some(notes.trim()).filter(|n| !n.is_empty())),
+        None => (line, None),
+    };
+    Some(parse_date(date.trim()).map(|d| ImportRow {
+        date: d,
+        notes: notes.map(str::to_string),
+    }))
+}
+
+async fn backfill(client: &ApiClient, allow_secrets: bool, dry_run: bool) -> Result<(), CliError> {
+    let my_id = client.current_user_id().await?;
+
+    let (mut done, mut failed, mut total) = (0, 0, 0);
+    for (index, line) in std::io::stdin().lines().enumerate() {
+        let line = line.map_err(|e| format!("Failed to read stdin: {}", e))?;
+        let row = parse_backfill_line(&line).map(|row| row.and_then(|r| check_secrets(r.notes.as_deref(), allow_secrets).map(|_| r)));
+        let ImportRow { date, notes } = match row {
+            None => continue,
+            Some(Ok(row)) => row,
+            Some(Err(e)) => {
+                total += 1;
+                failed += 1;
+                println!("  - line {}: failed ({})", index + 1, e);
+                continue;
+            }
+        };
+        total += 1;
+        let date = date.format("%Y-%m-%d").to_string();
+        if dry_run {
+            done += 1;
+            println!("  - {}: [dry-run] would check in", date);
+            continue;
+        }
+        match client.create_or_update_visit(my_id, &date, notes.as_deref()).await {
+            Ok(_) => {
+                done += 1;
+                println!("  - {}: checked in", date);
+            }
+            Err(e) => {
+                failed += 1;
+                println!("  - {}: failed ({})", date, e);
+            }
+        }
+    }
+
+    let prefix = if dry_run { "[dry-run] Would have backfilled" } else { "Backfilled" };
+    println!("{} {} of {} dates", prefix, done, total);
+    if failed > 0 {
+        return Err(format!("{} lines failed to backfill", failed).into());
+    }
+
+    Ok(())
+}
+
+async fn solo(client: &ApiClient, window: Window, json: bool) -> Result<(), CliError> {
+    let my_id = client.current_user_id().await?;
+    let total = (window.end - window.start).num_days() + 1;
+
+    let solo_days: Vec<String> = fetch_rosters(client, window.start, window.end)
+        .await?
+        .into_iter()
+        .filter(|(_, visits)| visits.len() == 1 && visits[0].person.id == my_id)
+        .map(|(date, _)| date.format("%Y-%m-%d").to_string())
+        .collect();
+
+    if json {
+        print_json(&solo_days, "dates")?;
+        return Ok(());
+    }
+
+    if solo_days.is_empty() {
+        outln!("You weren't the only one in the hub on any day in {}", window.label);
+        return Ok(());
+    }
+
+    outln!("Days you were the only one in the hub ({} of {} in {}):", solo_days.len(), total, window.label);
+    for date in solo_days {
+        outln!("  - {}", date);
+    }
+
+    Ok(())
+}
+
+fn config_show(cli: &Cli, sources: &HashMap<String, String>, json: bool) -> Result<(), String> {
+    let default = || "default".to_string();
+    let mut settings: Vec<(&str, String, String)> = Vec::new();
+
+    let token = match env_source("RC_TOKEN", sources) {
+        Some(source) => ("[redacted]".to_string(), source),
+        None => match (env::var_os("RC_TOKEN_FILE"), config_file()) {
+            (Some(file), _) => ("[redacted]".to_string(), PathBuf::from(file).display().to_string()),
+            (None, Some(path)) if read_config()?.contains_key("token") => ("[redacted]".to_string(), path.display().to_string()),
+            _ => ("(not set)".to_string(), "-".to_string()),
+        },
+    };
+    settings.push(("token", token.0, token.1));
+
+    settings.push((
+        "api_base",
+        env::var("TCURSE_API_BASE").unwrap_or_else(|_| tcurse::API_BASE.to_string()),
+        env_source("TCURSE_API_BASE", sources).unwrap_or_else(default),
+    ));
+
+    settings.push((
+        "timezone",
+        tcurse::hub_tz().name().to_string(),
+        env_source("TCURSE_TIMEZONE", sources).unwrap_or_else(default),
+    ));
+
+    let (start, end) = hub_hours()?;
+    settings.push((
+        "hub_hours",
+        format!("{}-{}", start, end),
+        env_source("TCURSE_HUB_HOURS", sources).unwrap_or_else(default),
+    ));
+
+    let (afternoon, evening) = period_bounds()?;
+    settings.push((
+        "periods",
+        format!("{},{}", afternoon, evening),
+        env_source("TCURSE_PERIODS", sources).unwrap_or_else(default),
+    ));
+
+    let (failures, cooldown) = circuit_breaker()?;
+    settings.push((
+        "circuit_breaker",
+        format!("{},{}", failures, cooldown),
+        env_source("TCURSE_CIRCUIT_BREAKER", sources).unwrap_or_else(default),
+    ));
+
+    settings.push((
+        "idempotency_keys",
+        env_flag("TCURSE_IDEMPOTENCY_KEYS").to_string(),
+        env_source("TCURSE_IDEMPOTENCY_KEYS", sources).unwrap_or_else(default),
+    ));
+
+    settings.push((
+        "append_notes",
+        env_flag("TCURSE_APPEND_NOTES").to_string(),
+        env_source("TCURSE_APPEND_NOTES", sources).unwrap_or_else(default),
+    ));
+
+    settings.push((
+        "require_notes",
+        env_flag("TCURSE_REQUIRE_NOTES").to_string(),
+        env_source("TCURSE_REQUIRE_NOTES", sources).unwrap_or_else(default),
+    ));
+
+    settings.push((
+        "default_notes",
+        env::var("TCURSE_DEFAULT_NOTES").unwrap_or_else(|_| "(none)".to_string()),
+        env_source("TCURSE_DEFAULT_NOTES", sources).unwrap_or_else(default),
+    ));
+
+    settings.push((
+        "retries",
+        resolve_retries(cli)?.to_string(),
+        match (cli.no_retry, cli.retries) {
+            (true, _) => "--no-retry".to_string(),
+            (false, Some(_)) => "--retries".to_string(),
+            (false, None) => env_source("TCURSE_RETRIES", sources).unwrap_or_else(default),
+        },
+    ));
+
+    let name_format = resolve_name_format(cli.name_format)?;
+    let name_source = match cli.name_format {
+        Some(_) => "--name-format".to_string(),
+        None => env_source("TCURSE_NAME_FORMAT", sources).unwrap_or_else(default),
+    };
+    settings.push((
+        "allow_secrets",
+        env_flag("TCURSE_ALLOW_SECRETS").to_string(),
+        env_source("TCURSE_ALLOW_SECRETS", sources).unwrap_or_else(default),
+    ));
+
+    settings.push((
+        "name_format",
+        name_format.to_possible_value().unwrap().get_name().to_string(),
+        name_source,
+    ));
+
+    let person_id = configured_person_id()?;
+    settings.push((
+        "person_id",
+        person_id.map_or("(looked up)".to_string(), |id| id.to_string()),
+        env_source("TCURSE_PERSON_ID", sources).unwrap_or_else(default),
+    ));
+
+    settings.push((
+        "format",
+        resolve_roster_format(None)?.to_possible_value().unwrap().get_name().to_string(),
+        env_source("TCURSE_FORMAT", sources).unwrap_or_else(default),
+    ));
+
+    settings.push((
+        "color",
+        resolve_color(cli.color)?.to_possible_value().unwrap().get_name().to_string(),
+        match cli.color {
+            Some(_) => "--color".to_string(),
+            None => env_source("TCURSE_COLOR", sources).unwrap_or_else(default),
+        },
+    ));
+
+    settings.push(("quiet", cli.quiet.to_string(), if cli.quiet { "--quiet".to_string() } else { default() }));
+
+    if json {
+        let entries: Vec<serde_json::Value> = settings
+            .iter()
+            .map(|(key, value, source)| serde_json::json!({ "key": key, "value": value, "source": source }))
+            .collect();
+        print_json(&entries, "config")?;
+    } else {
+        let key_width = settings.iter().map(|s| s.0.len()).max().unwrap_or(0).max(3);
+        let value_width = settings.iter().map(|s| s.1.len()).max().unwrap_or(0).max(5);
+        outln!("{:<kw$}  {:<vw$}  SOURCE", "KEY", "VALUE", kw = key_width, vw = value_width);
+        for (key, value, source) in &settings {
+            outln!("{:<kw$}  {:<vw$}  {}", key, value, source, kw = key_width, vw = value_width);
+        }
+    }
+
+    Ok(())
+}
+
+fn env_file_arg(args: &[String]) -> Option<PathBuf> {
+    let mut iter = args.iter().skip(1).take_while(|a| *a != "--");
+    while let Some(arg) = iter.next() {
+        if let Some(path) = arg.strip_prefix("--env-file=") {
+            return Some(PathBuf::from(path));
+        }
+        if arg == "--env-file" {
+            return iter.next().map(PathBuf::from);
+        }
+    }
+    None
+}
+
+fn subcommand_position(args: &[String]) -> Option<usize> {
+    let cmd = Cli::command();
+    let takes_value = |arg: &str| {
+        cmd.get_arguments().any(|a| {
+            let matches = match arg.strip_prefix("--") {
+                Some(long) => a.get_long() == Some(long),
+                None => arg.len() == 2 && a.get_short() == arg.chars().nth(1),
+            };
+            matches && a.get_action().takes_values()
+        })
+    };
+
+    let mut index = 1;
+    while index < args.len() {
+        let arg = &args[index];
+        if arg == "--" || !arg.starts_with('-') {
+            return (arg != "--").then_some(index);
+        }
+        if !arg.contains('=') && takes_value(arg) {
+            index += 1;
+        }
+        index += 1;
+    }
+    None
+}
+
+fn split_words(input: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    for c in input.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err(format!("Unterminated quote in alias '{}'", input));
+    }
+    if in_word {
+        words.push(current);
+    }
+    Ok(words)
+}
+
+fn alias_name(name: &str) -> String {
+    name.to_lowercase().replace('_', "-")
+}
+
+fn configured_aliases(config: &toml::Table) -> Result<HashMap<String, String>, String> {
+    let mut aliases = HashMap::new();
+    if let Some(section) = config.get("alias") {
+        let table = section.as_table().ok_or("The alias setting in config.toml must be a table, e.g. [alias] today = \"checked-in --mine\"")?;
+        for (name, value) in table {
+            let value = value.as_str().ok_or_else(|| format!("Alias '{}' in config.toml must be a string of arguments", name))?;
+            aliases.insert(alias_name(name), value.to_string());
+        }
+    }
+    for (key, value) in env::vars_os() {
+        if let (Some(key), Some(value)) = (key.to_str(), value.to_str()) {
+            if let Some(name) = key.strip_prefix("TCURSE_ALIAS_") {
+                aliases.insert(alias_name(name), value.to_string());
+            }
+        }
+    }
+    Ok(aliases)
+}
+
+fn expand_aliases(mut args: Vec<String>, aliases: &HashMap<String, String>) -> Result<Vec<String>, String> {
+    let mut chain: Vec<String> = Vec::new();
+    while let Some(pos) = subcommand_position(&args) {
+        let name = args[pos].clone();
+        if Cli::command().find_subcommand(&name).is_some() {
+            break;
+        }
+        let expansion = match aliases.get(&alias_name(&name)) {
+            Some(v) => v,
+            None => break,
+        };
+        if chain.contains(&name) {
+            chain.push(name);
+            return Err(format!("Alias cycle detected: {}", chain.join(" -> ")));
+        }
+        chain.push(name);
+        let words = split_words(expansion)?;
+        if words.is_empty() {
+            return Err(format!("Alias '{}' is empty", chain.last().unwrap()));
+        }
+        args.splice(pos..=pos, words);
+    }
+    Ok(args)
+}
+
+fn explain(cli: &Cli) -> Result<String, String> {
+    let me = match (cli.refresh_profile, configured_person_id()?) {
+        (false, Some(id)) => format!("Use person id {} from TCURSE_PERSON_ID instead of calling the API", id),
+        (false, None) => match get_token().ok().and_then(|token| read_profile_cache(&token)) {
+            Some(profile) => format!("Use person id {} from the cached profile instead of calling the API", profile.id),
+            None => "GET /profiles/me to look up your person id and cache it".to_string(),
+        },
+        (true, _) => "GET /profiles/me to look up your person id and cache it".to_string(),
+    };
+
+    let mut steps: Vec<String> = match &cli.command {
+        Commands::Checkin(CheckinArgs { from: Some(from), to: Some(to), yes, .. }) => {
+            let mut steps = vec![
+                me,
+                format!("GET /hub_visits?person_id=<you>&start_date={}&end_date={} to find your check-ins in the range", from, to),
+            ];
+            if cli.dry_run {
+                steps.push("Print the check-ins that would be removed, without removing them".to_string());
+            } else {
+                if !yes {
+                    steps.push("Ask for confirmation before removing anything".to_string());
+                }
+                steps.push("DELETE /hub_visits/<you>/<date> for each check-in found".to_string());
+            }
+            steps
+        }
+        Commands::Checkin(CheckinArgs { remove: true, date, .. }) => {
+            vec![me, format!("DELETE /hub_visits/<you>/{} to remove that day's check-in", get_date_string(date.clone()))]
+        }
+        Commands::Checkin(CheckinArgs { clear_notes: true, date, .. }) => {
+            let date = get_date_string(date.clone());
+            vec![
+                me,
+                format!("GET /hub_visits/<you>/{} to find your check-in", date),
+                format!("PATCH /hub_visits/<you>/{} with empty notes to clear them", date),
+            ]
+        }
+        Commands::Checkin(CheckinArgs { notes, notes_file, date, interactive, append, .. }) => {
+            let date = get_date_string(date.clone());
+            let mut steps = vec![me, format!("GET /hub_visits/<you>/{} to see whether you're already checked in", date)];
+            let mut notes = notes.clone().or_else(|| notes_file.as_ref().map(|p| format!("<contents of {}>", p.display())));
+            if *interactive {
+                steps.insert(0, "Prompt for notes if stdin is a terminal".to_string());
+                notes = Some("<what you type>".to_string());
+            }
+            match &notes {
+                Some(n) if *append || env_flag("TCURSE_APPEND_NOTES") => steps.push(format!("PATCH /hub_visits/<you>/{} with your existing notes followed by \"{}\"", date, n)),
+                Some(n) => steps.push(format!("PATCH /hub_visits/<you>/{} with notes \"{}\"", date, n)),
+                None => match env::var("TCURSE_DEFAULT_NOTES") {
+                    Ok(n) => steps.push(format!("PATCH /hub_visits/<you>/{} with default notes \"{}\" if you aren't checked in yet", date, n)),
+                    Err(_) => steps.push(format!("PATCH /hub_visits/<you>/{} if you aren't checked in yet", date)),
+                },
+            }
+            steps
+        }
+        Commands::CheckedIn(args) => {
+            let date = args.date.clone().unwrap_or_else(|| get_date_string(None));
+            let mut steps = match &args.from {
+                Some(from) => vec![format!(
+                    "GET /hub_visits?start_date={}&end_date={} to fetch every check-in in the range",
+                    from,
+                    args.to.clone().unwrap_or_else(|| get_date_string(None))
+                )],
+                None => vec![format!("GET /hub_visits?date={} to fetch the roster", date)],
+            };
+            if args.mine {
+                steps.push(me);
+                steps.push("Print only your own entry".to_string());
+            } else if args.others {
+                steps.push(me);
+                steps.push("Print everyone else who is checked in".to_string());
+            } else {
+                steps.push("Print everyone who is checked in".to_string());
+            }
+            if args.watch {
+                steps.push(format!("Repeat every {} seconds until stopped", args.interval));
+            }
+            steps
+        }
+        Commands::Whoami { .. } => vec![
+            "GET /profiles/me to look up the account your token belongs to".to_string(),
+            "Print its id and name".to_string(),
+        ],
+        Commands::Find { query, .. } => vec![
+            format!("GET /profiles?query={} to search profiles", query),
+            "Print the id and name of each match".to_string(),
+        ],
+        Commands::Batches { all, .. } => vec![
+            "GET /batches to fetch every batch".to_string(),
+            if *all {
+                "Print all batches, marking the one in session today".to_string()
+            } else {
+                "Print current and upcoming batches, marking the one in session today".to_string()
+            },
+        ],
+        Commands::Who { person, .. } => {
+            let lookup = if person.trim().parse::<i64>().is_ok() || person.contains('@') {
+                format!("GET /profiles/{} to fetch the profile", person.trim())
+            } else {
+                format!("GET /profiles?query={} and require exactly one match", person)
+            };
+            vec![lookup, "Print their pronouns, contact details, and batches".to_string()]
+        }
+        Commands::History(HistoryArgs { start, end, person, tag, first, last, .. }) => {
+            let mut steps = vec![
+                match person {
+                    Some(id) => format!("Use person id {}", id),
+                    None => me,
+                },
+                format!("GET /hub_visits/<person>/<day> for each day from {} to {}", start, end),
+                match tag {
+                    Some(tag) => format!("List the days with a check-in whose notes are tagged #{}", tag.trim_start_matches('#')),
+                    None => "List the days with a check-in".to_string(),
+                },
+            ];
+            match (first, last) {
+                (Some(n), _) => steps.push(format!("Keep only the earliest {} of those days", n)),
+                (_, Some(n)) => steps.push(format!("Keep only the most recent {} of those days", n)),
+                _ => {}
+            }
+            steps
+        }
+        Commands::Range { start, end, .. } => vec![
+            format!("GET /hub_visits?start_date={}&end_date={} to fetch every check-in in the range", start, end),
+            match output_format(cli)? {
+                Some(OutputFormat::Json) => "Print a JSON object mapping each day to its check-in count".to_string(),
+                Some(OutputFormat::Csv) => "Print one CSV row per check-in".to_string(),
+                _ => "Print how many people were checked in each day".to_string(),
+            },
+        ],
+        Commands::Summary { start, end, .. } => vec![
+            me,
+            format!("GET /hub_visits?start_date={}&end_date={} to fetch every check-in in the range", start, end),
+            "Print total check-ins, unique attendees, the busiest day, the daily average, and your own days".to_string(),
+        ],
+        Commands::Calendar { month, .. } => vec![
+            me,
+            format!(
+                "GET /hub_visits?start_date=<first day>&end_date=<last day> for {}, up to today",
+                month.clone().unwrap_or_else(|| tcurse::hub_today().format("%Y-%m").to_string())
+            ),
+            "Print a month grid of your check-ins and daily counts".to_string(),
+        ],
+        Commands::AreTheyIn { people, date } => {
+            let mut steps: Vec<String> = people
+                .iter()
+                .filter(|p| p.trim().parse::<i64>().is_err())
+                .map(|p| format!("GET /profiles?query={} to resolve the name to a person id", p.trim()))
+                .collect();
+            steps.push(format!("GET /hub_visits?date={} to fetch the roster", date.clone().unwrap_or_else(|| get_date_string(None))));
+            steps.push(format!("Report whether {} appear in it", people.join(", ")));
+            steps
+        }
+        Commands::IsIn { name } => vec![
+            format!("GET /hub_visits?date={} to fetch the roster", get_date_string(None)),
+            format!("Print everyone whose name contains \"{}\", or exit 1 if no one does", name),
+        ],
+        Commands::Tags { days, range, .. } => {
+            let Window { start, end, .. } = resolve_window(*days, range)?;
+            vec![
+                me,
+                format!("GET /hub_visits/<you>/<day> for each day from {} to {}", start, end),
+                "Count the #hashtags in your notes".to_string(),
+            ]
+        }
+        Commands::Login { .. } => vec![format!(
+            "Write the token to {} with owner-only permissions",
+            config_file().map_or("the config file".to_string(), |p| p.display().to_string())
+        )],
+        Commands::Config { command: ConfigCommand::Show { .. } } => {
+            vec!["Print the effective configuration from flags, environment, .env files, and config.toml".to_string()]
+        }
+        Commands::Config { command: ConfigCommand::List } => vec!["Print the settings saved in config.toml".to_string()],
+        Commands::Config { command: ConfigCommand::Get { key } } => vec![format!("Print {} from config.toml", key)],
+        Commands::Config { command: ConfigCommand::Set { key, value } } => {
+            vec![format!("Save {} = {} to config.toml", key, if key == "token" { "[redacted]" } else { value })]
+        }
+        Commands::Digest { date, .. } => vec![
+            format!("GET /hub_visits?date={} to fetch the roster", date.clone().unwrap_or_else(|| get_date_string(None))),
+            "Print a summary of who was in and their notes".to_string(),
+        ],
+        Commands::Gaps { days, range, .. } => {
+            let Window { start, end, .. } = resolve_window(*days, range)?;
+            vec![
+                me,
+                format!("GET /hub_visits?person_id=<you>&start_date={}&end_date={} to fetch your check-ins", start, end),
+                "Print the gaps between them".to_string(),
+            ]
+        }
+        Commands::Metrics { date, .. } => vec![
+            me,
+            format!("GET /hub_visits?date={} to fetch the roster", date.clone().unwrap_or_else(|| get_date_string(None))),
+            "Print Prometheus text-format gauges".to_string(),
+        ],
+        Commands::Daemon { interval, sink, .. } => vec![
+            format!("GET /hub_visits?date=<today> every {} seconds until stopped", interval),
+            match sink {
+                Sink::Stdout => "Print each arrival or departure as a JSON line".to_string(),
+                Sink::Webhook => "POST each arrival or departure as JSON to the webhook URL".to_string(),
+                Sink::Desktop => "Show a desktop notification for each arrival or departure".to_string(),
+            },
+        ],
+        Commands::Tui { interval, .. } => vec![
+            me,
+            format!("GET /hub_visits?date=<day> for the day on screen, again every {} seconds", interval),
+            if cli.dry_run {
+                "Describe check-in toggles and note edits instead of sending PATCH or DELETE requests (--dry-run)".to_string()
+            } else {
+                "PATCH or DELETE /hub_visits/<you>/<day> when you toggle your check-in or save notes".to_string()
+            },
+        ],
+        Commands::Inspect { path } => vec![
+            format!("GET /{} and print the inferred shape of the response", path.trim_start_matches('/')),
+        ],
+        Commands::Import { input, .. } => vec![
+            format!("Read dates and notes from {}", input.display()),
+            me,
+            "GET /hub_visits/<you>/<date> for each row to skip days you're already checked in".to_string(),
+            "PATCH /hub_visits/<you>/<date> for each remaining row".to_string(),
+        ],
+        Commands::Backfill { .. } => vec![
+            "Read dates and optional tab-separated notes from stdin".to_string(),
+            me,
+            "PATCH /hub_visits/<you>/<date> for each line".to_string(),
+        ],
+        Commands::Streak { days, range, .. } => {
+            let Window { start, end, .. } = resolve_window(*days, range)?;
+            vec![
+                me,
+                format!("GET /hub_visits?person_id=<you>&start_date={}&end_date={} to fetch your check-ins", start, end),
+                "Print your current and longest streaks".to_string(),
+            ]
+        }
+        Commands::Solo { days, range, .. } => {
+            let Window { start, end, .. } = resolve_window(*days, range)?;
+            vec![
+                me,
+                format!("GET /hub_visits?start_date={}&end_date={} to fetch every check-in in the range", start, end),
+                "List the days where you were the only one checked in".to_string(),
+            ]
+        }
+    };
+
+    let range_removal = matches!(&cli.command, Commands::Checkin(CheckinArgs { from: Some(_), .. }));
+    if cli.dry_run && !range_removal && matches!(&cli.command, Commands::Checkin(_) | Commands::Import { .. } | Commands::Backfill { .. }) {
+        steps.push("Print the PATCH or DELETE requests above instead of sending them (--dry-run)".to_string());
+    }
+
+    let mut out = String::from("This command would:\n");
+    for (i, step) in steps.iter().enumerate() {
+        out.push_str(&format!("  {}. {}\n", i + 1, step));
+    }
+    Ok(out)
+}
+
+async fn run(args: Vec<String>) -> Result<(), CliError> {
+    let sources = load_env(env_file_arg(&args).as_deref())?;
+    tcurse::set_hub_tz(hub_timezone()?);
+    let args = expand_aliases(args, &configured_aliases(&read_config().unwrap_or_default())?)?;
+    let cli = Cli::parse_from(&args);
+    init_logging(cli.verbose);
+    if let Some(path) = &cli.output_file {
+        open_output(path)?;
+    }
+    init_color(resolve_color(cli.color)?);
+    if cli.explain {
+        out!("{}", explain(&cli)?);
+        return Ok(());
+    }
+    if let Commands::Config { command } = &cli.command {
+        return Ok(match command {
+            ConfigCommand::Show { .. } => config_show(&cli, &sources, output_format(&cli)? == Some(OutputFormat::Json)),
+            ConfigCommand::List => config_list(),
+            ConfigCommand::Get { key } => config_get(key),
+            ConfigCommand::Set { key, value } => config_set(key, value),
+        }?);
+    }
+    if let Commands::Login { token } = cli.command {
+        return Ok(login(token)?);
+    }
+
+    let name_format = resolve_name_format(cli.name_format)?;
+    let token = get_token().map_err(CliError::Auth)?;
+    let (person_id, cached) = match (cli.refresh_profile, configured_person_id()?) {
+        (true, _) => (None, false),
+        (false, Some(id)) => (Some(id), false),
+        (false, None) => match read_profile_cache(&token) {
+            Some(profile) => (Some(profile.id), true),
+            None => (None, false),
+        },
+    };
+    let client = client_builder(&cli)?.token(token.clone()).build();
+
+    let mut result = dispatch(cli, &client.clone().with_person_id(person_id), name_format).await;
+    if let (true, Some(cached_id)) = (cached, person_id) {
+        if result.as_ref().is_err_and(unauthorized) {
+            clear_profile_cache();
+            if let Some(retried) = retry_with_fresh_profile(&args, &client, cached_id, name_format).await {
+                result = retried;
+            }
+        }
+    }
+    if let Some(profile) = client.fetched_profile() {
+        write_profile_cache(&token, profile);
+    }
+    result
+}
+
+fn client_builder(cli: &Cli) -> Result<tcurse::ApiClientBuilder, String> {
+    let mut builder = ApiClient::builder().retries(resolve_retries(cli)?);
+    if let Some(ms) = cli.retry_base_ms {
+        builder = builder.retry_base(std::time::Duration::from_millis(ms));
+    }
+    if let Ok(base_url) = env::var("TCURSE_API_BASE") {
+        builder = builder.base_url(base_url);
+    }
+    if matches!(&cli.command, Commands::Daemon { .. } | Commands::Tui { .. } | Commands::CheckedIn(CheckedInArgs { watch: true, .. })) {
+        let (failures, cooldown) = circuit_breaker()?;
+        builder = builder.circuit_breaker(failures, std::time::Duration::from_secs(cooldown));
+    }
+    Ok(builder.idempotency_keys(env_flag("TCURSE_IDEMPOTENCY_KEYS")))
+}
+
+async fn retry_with_fresh_profile(args: &[String], client: &ApiClient, cached_id: i64, name_format: NameFormat) -> Option<Result<(), CliError>> {
+    let me = client.get_current_user().await.ok().filter(|me| me.id != cached_id)?;
+    Some(dispatch(Cli::parse_from(args), &client.clone().with_person_id(Some(me.id)), name_format).await)
+}
+
+async fn dispatch(cli: Cli, client: &ApiClient, name_format: NameFormat) -> Result<(), CliError> {
+    let output = output_format(&cli)?;
+    let json = output == Some(OutputFormat::Json);
+    match cli.command {
+        Commands::Checkin(CheckinArgs { from: Some(from), to: Some(to), yes, .. }) => {
+            remove_range(client, &from, &to, yes, cli.dry_run, json).await
+        }
+        Commands::Checkin(args) => checkin(client, args, &CheckinSettings::from_env()?, cli.quiet, cli.dry_run, json).await,
+        Commands::CheckedIn(args) => get_checked_in(client, args, output, name_format, cli.quiet).await,
+        Commands::Calendar { month, ascii } => calendar(client, month, ascii, json).await,
+        Commands::AreTheyIn { people, date } => are_they_in(client, people, date, name_format, json).await,
+        Commands::Tags { days, range, .. } => tags(client, resolve_window(days, &range)?, json).await,
+        Commands::Digest { date, format } => digest(client, date, format, name_format, json).await,
+        Commands::Gaps { days, range, min, .. } => gaps(client, resolve_window(days, &range)?, min, json).await,
+        Commands::Metrics { date, person } => metrics(client, date, person).await,
+        Commands::Daemon { interval, sink, webhook_url, person } => daemon(client, interval, sink, webhook_url, person).await,
+        Commands::Tui { interval, force } => tui(client, interval, name_format, force || env_flag("TCURSE_ALLOW_SECRETS"), cli.dry_run).await,
+        Commands::Inspect { path } => inspect(client, &path).await,
+        Commands::Whoami { .. } => whoami(client, json).await,
+        Commands::IsIn { name } => is_in(client, &name, cli.quiet, json).await,
+        Commands::Find { query, .. } => find(client, &query, json).await,
+        Commands::Who { person, .. } => who(client, &person, json).await,
+        Commands::Batches { all, .. } => batches(client, all, json).await,
+        Commands::History(args) => history(client, args, json).await,
+        Commands::Range { start, end, .. } => range(client, &start, &end, output).await,
+        Commands::Summary { start, end, .. } => summary(client, &start, &end, json).await,
+        Commands::Import { input, force } => import(client, &input, force || env_flag("TCURSE_ALLOW_SECRETS"), cli.dry_run).await,
+        Commands::Backfill { force } => backfill(client, force || env_flag("TCURSE_ALLOW_SECRETS"), cli.dry_run).await,
+        Commands::Streak { days, range, skip_weekends, .. } => streak(client, resolve_window(days, &range)?, skip_weekends, json).await,
+        Commands::Solo { days, range, .. } => solo(client, resolve_window(days, &range)?, json).await,
+        Commands::Config { .. } | Commands::Login { .. } => unreachable!(),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let result = run(env::args().collect()).await;
+    let result = match finish_output(result.is_ok()) {
+        Ok(()) => result,
+        Err(e) => result.and(Err(e.into())),
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("tcurse-test-{}-{}", std::process::id(), name))
+    }
+
+    fn visit(id: i64, name: &str) -> HubVisit {
+        serde_json::from_value(serde_json::json!({ "date": "2024-01-15", "person": { "id": id, "name": name } })).unwrap()
+    }
+
+    fn profile(id: i64, name: &str) -> Profile {
+        serde_json::from_value(serde_json::json!({ "id": id, "name": name })).unwrap()
+    }
+
+    type Requests = std::sync::Arc<Mutex<Vec<String>>>;
+
+    async fn mock_api(handler: fn(&str) -> (u16, String)) -> (ApiClient, Requests) {
+        let (base_url, requests) = mock_base_url(handler).await;
+        (ApiClient::with_base_url("test-token".to_string(), base_url).with_person_id(Some(7)), requests)
+    }
+
+    async fn mock_base_url(handler: fn(&str) -> (u16, String)) -> (String, Requests) {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Requests::default();
+        let log = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut reader = BufReader::new(stream);
+                let mut request = String::new();
+                reader.read_line(&mut request).await.unwrap();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).await.unwrap();
+                    match line.trim_end().split_once(':') {
+                        Some((k, v)) if k.eq_ignore_ascii_case("content-length") => length = v.trim().parse().unwrap(),
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+                reader.read_exact(&mut vec![0; length]).await.unwrap();
+
+                let request: Vec<&str> = request.split_whitespace().take(2).collect();
+                let request = request.join(" ");
+                let (status, body) = handler(&request);
+                log.lock().unwrap().push(request);
+                let response = format!("HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body);
+                reader.into_inner().write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (base_url, requests)
+    }
+
+    fn my_visits(dates: &[&str]) -> String {
+        let visits: Vec<serde_json::Value> = dates
+            .iter()
+            .map(|date| serde_json::json!({ "date": date, "person": { "id": 7, "name": "Ada" } }))
+            .collect();
+        serde_json::Value::Array(visits).to_string()
+    }
+
+    #[tokio::test]
+    async fn no_retry_makes_a_single_attempt_against_a_503() {
+        let (base_url, requests) = mock_base_url(|_| (503, String::new())).await;
+        let retrying = Cli::parse_from(["tcurse", "--retries", "2", "--retry-base-ms", "1", "whoami"]);
+        let client = client_builder(&retrying).unwrap().token("test-token").base_url(base_url.clone()).build();
+        assert!(client.get_current_user().await.is_err());
+        assert_eq!(requests.lock().unwrap().len(), 3);
+
+        requests.lock().unwrap().clear();
+        let cli = Cli::parse_from(["tcurse", "--no-retry", "whoami"]);
+        let client = client_builder(&cli).unwrap().token("test-token").base_url(base_url).build();
+        assert_eq!(client.retries(), 0);
+        assert!(client.get_current_user().await.is_err());
+        assert_eq!(*requests.lock().unwrap(), ["GET /profiles/me"]);
+    }
+
+    #[test]
+    fn retry_flags_are_validated() {
+        for args in [
+            vec!["--retries", "11"],
+            vec!["--retries", "-1"],
+            vec!["--retry-base-ms", "0"],
+            vec!["--retry-base-ms", "10001"],
+            vec!["--no-retry", "--retries", "2"],
+            vec!["--no-retry", "--retry-base-ms", "50"],
+        ] {
+            assert!(Cli::try_parse_from(["tcurse"].into_iter().chain(args.clone()).chain(["whoami"])).is_err(), "{:?}", args);
+        }
+        assert!(Cli::try_parse_from(["tcurse", "--retries", "10", "--retry-base-ms", "10000", "whoami"]).is_ok());
+        assert!(parse_config_value("retries", "11").is_err());
+    }
+
+    fn output_of(args: &[&str]) -> Result<Option<OutputFormat>, String> {
+        output_format(&Cli::parse_from(["tcurse", "--quiet"].iter().chain(args)))
+    }
+
+    #[test]
+    fn deprecated_format_flags_map_onto_output() {
+        assert_eq!(output_of(&["whoami", "--json"]), Ok(Some(OutputFormat::Json)));
+        assert_eq!(output_of(&["--output", "json", "history", "7d", "today", "--json"]), Ok(Some(OutputFormat::Json)));
+        assert_eq!(output_of(&["range", "7d", "today", "--format", "csv"]), Ok(Some(OutputFormat::Csv)));
+        assert_eq!(output_of(&["range", "7d", "today", "--format", "counts"]), Ok(Some(OutputFormat::Text)));
+        assert_eq!(output_of(&["checked-in", "--format", "json"]), Ok(Some(OutputFormat::Json)));
+        assert_eq!(output_of(&["config", "show", "--format", "json"]), Ok(Some(OutputFormat::Json)));
+        assert_eq!(output_of(&["checked-in", "--format", "table"]), Ok(None));
+        assert_eq!(output_of(&["calendar", "--output", "json"]), Ok(Some(OutputFormat::Json)));
+        assert_eq!(output_of(&["calendar"]), Ok(None));
+    }
+
+    #[test]
+    fn conflicting_or_unsupported_output_is_rejected() {
+        let err = output_of(&["--output", "text", "whoami", "--json"]).unwrap_err();
+        assert_eq!(err, "--json conflicts with --output text");
+        assert!(output_of(&["--output", "json", "range", "7d", "today", "--format", "csv"]).is_err());
+        for command in [&["calendar"][..], &["is-in", "ada"], &["digest"], &["checkin", "--remove"]] {
+            let err = output_of(&[&["--output", "csv"][..], command].concat()).unwrap_err();
+            assert!(err.contains("only supported by range and checked-in"), "{}", err);
+        }
+        assert_eq!(output_of(&["--output", "csv", "checked-in"]), Ok(Some(OutputFormat::Csv)));
+        assert!(Cli::try_parse_from(["tcurse", "--output", "yaml", "calendar"]).is_err());
+    }
+
+    #[test]
+    fn output_overrides_only_the_roster_format_it_names() {
+        assert!(matches!(roster_format(Some(OutputFormat::Text), Some(RosterFormat::Table)), Ok(RosterFormat::Table)));
+        assert!(matches!(roster_format(Some(OutputFormat::Json), Some(RosterFormat::Table)), Ok(RosterFormat::Json)));
+        assert!(matches!(roster_format(Some(OutputFormat::Csv), None), Ok(RosterFormat::Csv)));
+        assert!(matches!(roster_format(None, Some(RosterFormat::List)), Ok(RosterFormat::List)));
+    }
+
+    #[test]
+    fn pick_profile_takes_a_single_match() {
+        assert_eq!(pick_profile("al", vec![profile(1, "Alice Smith")]).unwrap().id, 1);
+    }
+
+    #[test]
+    fn pick_profile_prefers_an_exact_name() {
+        let matches = vec![profile(1, "Al Jones"), profile(2, "Alice Smith"), profile(3, "Sal Reyes")];
+        assert_eq!(pick_profile("al jones", matches).unwrap().id, 1);
+    }
+
+    #[test]
+    fn pick_profile_reports_ambiguous_and_missing_names() {
+        let err = pick_profile("al", vec![profile(2, "Alice Smith"), profile(3, "Sal Reyes")]).unwrap_err();
+        assert!(err.contains("2 people match 'al'"), "{}", err);
+        assert!(err.contains("Alice Smith (2)") && err.contains("Sal Reyes (3)"), "{}", err);
+        assert_eq!(pick_profile("zed", Vec::new()).unwrap_err(), "No matches for 'zed'");
+    }
+
+    #[test]
+    fn saving_a_config_value_keeps_other_keys_and_replaces_an_unparsable_file() {
+        let path = temp_path("509").join("config.toml");
+        save_config_value(&path, "retries", toml::Value::Integer(2)).unwrap();
+        save_config_value(&path, "token", toml::Value::String("abc".to_string())).unwrap();
+        let saved: toml::Table = std::fs::read_to_string(&path).unwrap().parse().unwrap();
+        assert_eq!(saved.get("retries"), Some(&toml::Value::Integer(2)));
+        assert_eq!(saved.get("token"), Some(&toml::Value::String("abc".to_string())));
+
+        std::fs::write(&path, "token = ").unwrap();
+        save_config_value(&path, "token", toml::Value::String("def".to_string())).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "token = \"def\"\n");
+        std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn unresolvable_directories_disable_files_instead_of_failing() {
+        assert_eq!(tcurse_dir(None, "config"), None);
+        assert_eq!(tcurse_dir(None, "cache"), None);
+        assert_eq!(tcurse_dir(Some(PathBuf::from("/home/ada/.config")), "config"), Some(PathBuf::from("/home/ada/.config/tcurse")));
+    }
+
+    #[test]
+    fn earlier_env_files_win_and_set_variables_are_kept() {
+        let (first, second) = (temp_path("first.env"), temp_path("second.env"));
+        std::fs::write(&first, "TCURSE_TEST_206_A=first\nTCURSE_TEST_206_B=first\n").unwrap();
+        std::fs::write(&second, "TCURSE_TEST_206_B=second\nTCURSE_TEST_206_C=second\n").unwrap();
+        env::set_var("TCURSE_TEST_206_C", "preset");
+
+        let files = [&first, &second]
+            .iter()
+            .map(|p| (p.display().to_string(), dotenvy::from_path_iter(p).unwrap()))
+            .collect();
+        let sources = apply_env_files(files);
+        std::fs::remove_file(&first).ok();
+        std::fs::remove_file(&second).ok();
+
+        assert_eq!(env::var("TCURSE_TEST_206_A").unwrap(), "first");
+        assert_eq!(env::var("TCURSE_TEST_206_B").unwrap(), "first");
+        assert_eq!(env::var("TCURSE_TEST_206_C").unwrap(), "preset");
+        assert_eq!(sources.get("TCURSE_TEST_206_B"), Some(&first.display().to_string()));
+        assert!(!sources.contains_key("TCURSE_TEST_206_C"));
+    }
+
+    #[test]
+    fn my_entry_finds_me_in_the_roster() {
+        let roster = vec![visit(1, "Ada Lovelace"), visit(2, "Grace Hopper")];
+        assert_eq!(my_entry(roster, 2, "2024-01-15").unwrap().person.name, "Grace Hopper");
+    }
+
+    #[test]
+    fn my_entry_fails_when_i_am_absent() {
+        let roster = vec![visit(1, "Ada Lovelace")];
+        assert_eq!(my_entry(roster, 2, "2024-01-15").unwrap_err(), "You are not checked in for 2024-01-15");
+        assert!(my_entry(Vec::new(), 2, "2024-01-15").is_err());
+    }
+
+    #[test]
+    fn required_notes_must_be_given() {
+        assert!(checkin_notes(None, None, true).is_err());
+        assert!(checkin_notes(Some("  ".to_string()), None, true).is_err());
+        assert_eq!(checkin_notes(Some("pairing".to_string()), None, true), Ok(Some("pairing".to_string())));
+        assert_eq!(checkin_notes(None, None, false), Ok(None));
+    }
+
+    #[test]
+    fn default_notes_satisfy_the_requirement() {
+        assert_eq!(checkin_notes(None, Some("at the hub".to_string()), true), Ok(Some("at the hub".to_string())));
+        assert_eq!(
+            checkin_notes(Some("pairing".to_string()), Some("at the hub".to_string()), true),
+            Ok(Some("pairing".to_string()))
+        );
+    }
+
+    #[test]
+    fn names_render_in_each_format() {
+        let name = "Ada  King Lovelace ";
+        assert_eq!(format_name(name, NameFormat::Full), "Ada  King Lovelace");
+        assert_eq!(format_name(name, NameFormat::First), "Ada");
+        assert_eq!(format_name(name, NameFormat::Initials), "A.K.L.");
+    }
+
+    #[test]
+    fn single_word_and_empty_names_render() {
+        assert_eq!(format_name("Cher", NameFormat::Full), "Cher");
+        assert_eq!(format_name("Cher", NameFormat::First), "Cher");
+        assert_eq!(format_name("cher", NameFormat::Initials), "C.");
+        assert_eq!(format_name("", NameFormat::First), "");
+        assert_eq!(format_name("", NameFormat::Initials), "");
+    }
+
+    #[tokio::test]
+    async fn remove_range_deletes_each_checked_in_day() {
+        let (client, requests) = mock_api(|request| match request.split_once(' ') {
+            Some(("GET", _)) => (200, my_visits(&["2024-01-15", "2024-01-17"])),
+            Some(("DELETE", path)) if path.ends_with("2024-01-17") => (404, String::new()),
+            _ => (204, String::new()),
+        })
+        .await;
+        remove_range(&client, "2024-01-14", "2024-01-18", true, false, false).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0].starts_with("GET /hub_visits?person_id=7&start_date=2024-01-14&end_date=2024-01-18"), "{}", requests[0]);
+        assert_eq!(requests[1..], ["DELETE /hub_visits/7/2024-01-15", "DELETE /hub_visits/7/2024-01-17"]);
+    }
+
+    #[tokio::test]
+    async fn remove_range_reports_failed_days() {
+        let (client, requests) = mock_api(|request| match request.split_once(' ') {
+            Some(("GET", _)) => (200, my_visits(&["2024-01-15", "2024-01-16"])),
+            Some(("DELETE", path)) if path.ends_with("2024-01-15") => (400, "{}".to_string()),
+            _ => (204, String::new()),
+        })
+        .await;
+        let err = remove_range(&client, "2024-01-15", "2024-01-16", true, false, false).await.unwrap_err();
+        assert_eq!(err.to_string(), "1 removals failed");
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn remove_range_dry_run_deletes_nothing() {
+        let (client, requests) = mock_api(|_| (200, my_visits(&["2024-01-15"]))).await;
+        remove_range(&client, "2024-01-15", "2024-01-16", false, true, false).await.unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn remove_range_rejects_a_reversed_range() {
+        let (client, requests) = mock_api(|_| (200, "[]".to_string())).await;
+        assert!(remove_range(&client, "2024-01-16", "2024-01-15", true, false, false).await.is_err());
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn metrics_render_in_prometheus_text_format() {
+        let visits = vec![visit(1, "Ada"), visit(7, "Grace")];
+        assert_eq!(
+            render_metrics("2024-01-15", &visits, 7, &[1, 2]),
+            "# HELP tcurse_checked_in_total Number of people checked in to the hub.
+# TYPE tcurse_checked_in_total gauge
+tcurse_checked_in_total{date=\"2024-01-15\"} 2
+# HELP tcurse_my_checked_in Whether you are checked in to the hub.
+# TYPE tcurse_my_checked_in gauge
+tcurse_my_checked_in{date=\"2024-01-15\"} 1
+# HELP tcurse_person_checked_in Whether a person is checked in to the hub.
+# TYPE tcurse_person_checked_in gauge
+tcurse_person_checked_in{date=\"2024-01-15\",person_id=\"1\"} 1
+tcurse_person_checked_in{date=\"2024-01-15\",person_id=\"2\"} 0
+"
+        );
+    }
+
+    #[test]
+    fn metrics_skip_person_gauges_without_people() {
+        let text = render_metrics("2024-01-15", &[], 7, &[]);
+        assert!(text.contains("tcurse_checked_in_total{date=\"2024-01-15\"} 0\n"), "{}", text);
+        assert!(text.contains("tcurse_my_checked_in{date=\"2024-01-15\"} 0\n"), "{}", text);
+        assert!(!text.contains("tcurse_person_checked_in"), "{}", text);
+    }
+
+    #[test]
+    fn import_csv_parses_rows_and_flags_malformed_ones() {
+        let csv = "date,notes\n2024-01-15, pairing \n\n2024-13-01,bad month\nnot a date\n2024-01-16\n2024-01-17,\n";
+        let rows = parse_import_csv(csv);
+        let date = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+
+        assert_eq!(rows.len(), 5);
+        assert_eq!(rows[0], (2, Ok(ImportRow { date: date("2024-01-15"), notes: Some("pairing".to_string()) })));
+        assert_eq!(rows[1].0, 4);
+        assert!(rows[1].1.is_err());
+        assert_eq!(rows[2].0, 5);
+        assert!(rows[2].1.is_err());
+        assert_eq!(rows[3], (6, Ok(ImportRow { date: date("2024-01-16"), notes: None })));
+        assert_eq!(rows[4], (7, Ok(ImportRow { date: date("2024-01-17"), notes: None })));
+    }
+
+    #[tokio::test]
+    async fn imports_skip_secret_like_notes_unless_allowed() {
+        let path = temp_path("240.csv");
+        std::fs::write(&path, "date,notes\n2024-01-15,password=hunter2\n").unwrap();
+        let (client, requests) = mock_api(|request| match request.split_once(' ') {
+            Some(("PATCH", _)) => (200, r#"{"date":"2024-01-15","person":{"id":7,"name":"Ada"}}"#.to_string()),
+            _ => (404, String::new()),
+        })
+        .await;
+
+        assert!(import(&client, &path, false, false).await.is_err());
+        assert!(requests.lock().unwrap().is_empty());
+        import(&client, &path, true, false).await.unwrap();
+        assert_eq!(requests.lock().unwrap().last().unwrap(), "PATCH /hub_visits/7/2024-01-15");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn secret_checks_honor_the_override() {
+        assert!(check_secrets(Some("token=abc"), false).unwrap_err().contains("pass --force"));
+        assert_eq!(check_secrets(Some("token=abc"), true), Ok(()));
+        assert_eq!(check_secrets(Some("pairing on rust"), false), Ok(()));
+        assert_eq!(check_secrets(None, false), Ok(()));
+    }
+
+    #[test]
+    fn import_csv_without_a_header_keeps_the_first_row() {
+        let rows = parse_import_csv("2024-01-15,solo\n");
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].1.is_ok());
+    }
+
+    fn argv(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn aliases(entries: &[(&str, &str)]) -> HashMap<String, String> {
+        entries.iter().map(|(name, args)| (name.to_string(), args.to_string())).collect()
+    }
+
+    #[test]
+    fn aliases_expand_in_place_and_keep_the_remaining_args() {
+        let aliases = aliases(&[("today", "checked-in --date today --format 'json'"), ("mine", "today --mine")]);
+        assert_eq!(
+            expand_aliases(argv("tcurse -q mine --quiet"), &aliases).unwrap(),
+            argv("tcurse -q checked-in --date today --format json --mine --quiet")
+        );
+        assert_eq!(expand_aliases(argv("tcurse --retries 2 today"), &aliases).unwrap(), argv("tcurse --retries 2 checked-in --date today --format json"));
+    }
+
+    #[test]
+    fn builtin_commands_and_unknown_names_are_left_alone() {
+        let aliases = aliases(&[("whoami", "checked-in")]);
+        assert_eq!(expand_aliases(argv("tcurse whoami --json"), &aliases).unwrap(), argv("tcurse whoami --json"));
+        assert_eq!(expand_aliases(argv("tcurse no-such-alias"), &aliases).unwrap(), argv("tcurse no-such-alias"));
+    }
+
+    #[test]
+    fn alias_cycles_are_rejected() {
+        let aliases = aliases(&[("ping", "pong --json"), ("pong", "ping")]);
+        assert_eq!(expand_aliases(argv("tcurse ping"), &aliases).unwrap_err(), "Alias cycle detected: ping -> pong -> ping");
+    }
+
+    #[test]
+    fn empty_and_unterminated_aliases_are_rejected() {
+        let aliases = aliases(&[("empty", "  "), ("quote", "checked-in --date 'today")]);
+        assert_eq!(expand_aliases(argv("tcurse empty"), &aliases).unwrap_err(), "Alias 'empty' is empty");
+        assert!(expand_aliases(argv("tcurse quote"), &aliases).unwrap_err().starts_with("Unterminated quote"));
+    }
+
+    #[test]
+    fn config_aliases_are_read_from_the_alias_table() {
+        let config: toml::Table = "[alias]\ntoday = \"checked-in --date today\"\nMy_Week = \"summary 7d today\"\n".parse().unwrap();
+        let aliases = configured_aliases(&config).unwrap();
+        assert_eq!(aliases.get("today").map(String::as_str), Some("checked-in --date today"));
+        assert_eq!(expand_aliases(argv("tcurse my-week"), &aliases).unwrap(), argv("tcurse summary 7d today"));
+
+        assert!(configured_aliases(&"alias = \"today\"".parse().unwrap()).is_err());
+        assert!(configured_aliases(&"[alias]\ntoday = 1".parse().unwrap()).unwrap_err().contains("'today'"));
+    }
+
+    fn settings() -> CheckinSettings {
+        CheckinSettings { require_notes: false, default_notes: None, append_notes: false, allow_secrets: false, hub_hours: (0, 24) }
+    }
+
+    fn checkin_args(args: &[&str]) -> CheckinArgs {
+        match Cli::parse_from(["tcurse", "checkin"].iter().chain(args)).command {
+            Commands::Checkin(args) => args,
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn checkin_with_a_configured_person_id_never_fetches_my_profile() {
+        let (client, requests) = mock_api(|request| match request.split_once(' ') {
+            Some(("PATCH", _)) => (200, r#"{"date":"2024-01-15","person":{"id":7,"name":"Ada"}}"#.to_string()),
+            _ => (404, String::new()),
+        })
+        .await;
+        checkin(&client, checkin_args(&["--date", "2024-01-15", "--notes", "pairing"]), &settings(), true, false, false).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert!(requests.iter().all(|r| !r.contains("/profiles/me")), "{:?}", requests);
+        assert_eq!(requests.last().unwrap(), "PATCH /hub_visits/7/2024-01-15");
+    }
+
+    #[tokio::test]
+    async fn default_notes_leave_an_existing_check_in_alone() {
+        let (client, requests) = mock_api(|_| (200, r#"{"date":"2024-01-16","notes":"pairing","person":{"id":7,"name":"Ada"}}"#.to_string())).await;
+        let settings = CheckinSettings { default_notes: Some("at the hub".to_string()), ..settings() };
+        checkin(&client, checkin_args(&["--date", "2024-01-16"]), &settings, true, false, false).await.unwrap();
+        assert_eq!(*requests.lock().unwrap(), ["GET /hub_visits/7/2024-01-16"]);
+    }
+
+    #[tokio::test]
+    async fn required_notes_stop_a_check_in_before_any_request() {
+        let (client, requests) = mock_api(|_| (200, String::new())).await;
+        let settings = CheckinSettings { require_notes: true, ..settings() };
+        let err = checkin(&client, checkin_args(&["--date", "2024-01-16"]), &settings, true, false, false).await.unwrap_err();
+        assert!(err.to_string().starts_with("Notes are required"), "{}", err);
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    fn explained(args: &[&str]) -> String {
+        let cli = Cli::parse_from(["tcurse", "--explain"].iter().chain(args));
+        explain(&cli).unwrap()
+    }
+
+    #[test]
+    fn explain_lists_the_check_in_requests() {
+        let text = explained(&["--refresh-profile", "checkin", "--date", "2024-01-15", "--notes", "pairing"]);
+        assert!(text.starts_with("This command would:\n  1. GET /profiles/me"), "{}", text);
+        assert!(text.contains("2. GET /hub_visits/<you>/2024-01-15"), "{}", text);
+        assert!(text.contains("3. PATCH /hub_visits/<you>/2024-01-15 with notes \"pairing\""), "{}", text);
+
+        let text = explained(&["checkin", "--remove", "--date", "2024-01-15"]);
+        assert!(text.contains("DELETE /hub_visits/<you>/2024-01-15"), "{}", text);
+
+        let text = explained(&["--dry-run", "checkin", "--date", "2024-01-15", "--notes", "pairing"]);
+        assert!(text.contains("instead of sending them (--dry-run)"), "{}", text);
+    }
+
+    #[test]
+    fn explain_lists_the_lookup_requests() {
+        assert!(explained(&["checked-in", "--date", "2024-01-15"]).contains("GET /hub_visits?date=2024-01-15"));
+        assert!(explained(&["who", "42"]).contains("GET /profiles/42 to fetch the profile"));
+        assert!(explained(&["who", "ada"]).contains("GET /profiles?query=ada and require exactly one match"));
+        assert!(explained(&["batches"]).contains("GET /batches"));
+        assert!(explained(&["whoami"]).contains("GET /profiles/me"));
+    }
+
+    fn window(days: u32, during: Option<&str>) -> Result<Window, String> {
+        resolve_window(days, &RangeArgs { during: during.map(str::to_string), since: None })
+    }
+
+    #[test]
+    fn days_windows_end_today() {
+        let today = tcurse::hub_today();
+        let w = window(7, None).unwrap();
+        assert_eq!((w.start, w.end), (today - Duration::days(6), today));
+        assert_eq!(window(0, None).unwrap().start, today);
+        assert!(window(u32::MAX, None).unwrap_err().starts_with("--days 4294967295"));
+    }
+
+    #[test]
+    fn during_windows_cover_past_months_and_reject_future_ones() {
+        let w = window(30, Some("2024-02")).unwrap();
+        assert_eq!((w.start.to_string(), w.end.to_string(), w.label.as_str()), ("2024-02-01".to_string(), "2024-02-29".to_string(), "2024-02"));
+        assert!(window(30, Some("9999-01")).unwrap_err().contains("is in the future"));
+    }
+
+    #[test]
+    fn calendar_months_default_to_this_month_and_reject_future_ones() {
+        let date = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+        let today = date("2024-03-10");
+        assert_eq!(calendar_month(None, today), Ok((date("2024-03-01"), date("2024-03-31"))));
+        assert_eq!(calendar_month(Some("2024-02"), today), Ok((date("2024-02-01"), date("2024-02-29"))));
+        assert_eq!(calendar_month(Some("2024-04"), today).unwrap_err(), "2024-04 is in the future; the latest month with check-ins is 2024-03");
+        assert_eq!(calendar_month(Some("2024-03-05"), today).unwrap_err(), "Invalid month format. Use YYYY-MM");
+    }
+
+    #[test]
+    fn secret_like_notes_are_flagged() {
+        let cases = [
+            ("oops RC_TOKEN=abc123", "an assignment to a token, password, or key"),
+            ("db password=hunter2", "an assignment to a token, password, or key"),
+            ("(ghp_0123456789abcdefghijABCD)", "a known API token prefix"),
+            ("AKIAABCDEFGHIJ012345 leaked", "an AWS access key id"),
+            ("sha 0123456789abcdef0123456789abcdef", "a long hex string"),
+            ("key: dGhpcyBpcyBOb3QgYSByZWFsIHNlY3JldCAxMjM0NTY3OA==", "a long base64-like string"),
+        ];
+        for (notes, reason) in cases {
+            assert_eq!(secret_reason(notes), Some(reason), "{}", notes);
+        }
+    }
+
+    #[test]
+    fn ordinary_notes_are_not_flagged() {
+        let cases = [
+            "Pairing on the token bucket rate limiter",
+            "password = reset tomorrow",
+            "reading https://github.com/rust-lang/rust/pull/12345",
+            "commit deadbeef, then sk-learn notebook",
+            "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+            "Implementing_a_compiler_for_the_Lox_language_in_Rust",
+            "",
+        ];
+        for notes in cases {
+            assert_eq!(secret_reason(notes), None, "{}", notes);
+        }
+    }
+
+    #[test]
+    fn shapes_merge_fields_across_array_elements() {
+        let sample = serde_json::json!([
+            { "id": 1, "name": "Ada", "notes": null, "score": 1, "value": 1, "tags": ["rust"], "person": { "id": 1 } },
+            { "id": 2, "name": "Grace", "notes": "hi", "score": 1.5, "value": "x", "tags": [], "person": { "id": 2, "name": "Grace" }, "extra": true }
+        ]);
+        assert_eq!(
+            render_shape(&infer_shape(&sample), 0),
+            "array of {
+  extra: boolean (optional)
+  id: integer
+  name: string
+  notes: string | null
+  person: {
+    id: integer
+    name: string (optional)
+  }
+  score: number
+  tags: array of string
+  value: integer | string
+}"
+        );
+    }
+
+    #[test]
+    fn shapes_of_scalars_and_empty_values() {
+        let render = |value: serde_json::Value| render_shape(&infer_shape(&value), 0);
+        assert_eq!(render(serde_json::json!(null)), "null");
+        assert_eq!(render(serde_json::json!([])), "array of unknown");
+        assert_eq!(render(serde_json::json!({})), "{\n}");
+        assert_eq!(render(serde_json::json!([1, null])), "array of integer | null");
+    }
+
+    #[test]
+    fn output_files_are_replaced_only_on_success() {
+        let path = temp_path("output.txt");
+        std::fs::write(&path, "previous\n").unwrap();
+
+        let mut failed = OutputFile::create(&path).unwrap();
+        failed.file.write_all(b"partial\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "previous\n");
+        failed.finish(false).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "previous\n");
+        assert!(!failed.temp.exists());
+
+        let mut output = OutputFile::create(&path).unwrap();
+        output.file.write_all(b"roster\n").unwrap();
+        output.finish(true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "roster\n");
+        assert!(!output.temp.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn output_files_need_a_writable_file_path() {
+        assert!(OutputFile::create(Path::new("/")).is_err());
+        assert!(OutputFile::create(&temp_path("missing-dir").join("out.txt")).is_err());
+    }
+
+    #[test]
+    fn since_windows_include_today() {
+        let since = |spec: &str| resolve_window(30, &RangeArgs { during: None, since: Some(spec.to_string()) });
+        let today = tcurse::hub_today();
+        let w = since("2w").unwrap();
+        assert_eq!((w.start, w.end, w.label.as_str()), (today - Duration::days(13), today, "the last 2w"));
+        assert!(since("99999999d").unwrap_err().starts_with("--since 99999999d"));
+        assert!(since("999999999999999d").is_err());
+    }
+
+    #[test]
+    fn today_is_the_hub_date_even_when_the_local_date_is_ahead() {
+        let clock = tcurse::time::FixedClock("2024-03-10T03:30:00Z".parse().unwrap());
+        let today = tcurse::time::hub_today_with(&clock);
+        assert_eq!(today.to_string(), "2024-03-09");
+
+        assert_eq!(relative_date_on("today", today).unwrap(), "2024-03-09");
+        assert_eq!(relative_date_on("yesterday", today).unwrap(), "2024-03-08");
+        assert_eq!(relative_date_on("1w", today).unwrap(), "2024-03-02");
+        assert!(parse_date_on("2024-03-09", today).is_ok());
+        assert_eq!(parse_date_on("2024-03-10", today).unwrap_err(), "2024-03-10 is in the future");
+    }
+
+    #[test]
+    fn default_dates_always_pass_the_future_check() {
+        assert!(parse_date(&get_date_string(None)).is_ok());
+        assert!(parse_date(&relative_date("today").unwrap()).is_ok());
+    }
+
+    #[test]
+    fn dates_before_the_hub_existed_are_rejected() {
+        let today = NaiveDate::from_ymd_opt(2024, 1, 15).unwrap();
+        assert!(parse_date_on("2010-12-31", today).unwrap_err().contains("before the Recurse Center existed"));
+        assert!(parse_date_on("2011-01-01", today).is_ok());
+        assert_eq!(parse_date_on("15/01/2024", today).unwrap_err(), "Invalid date '15/01/2024'. Use YYYY-MM-DD");
+    }
+
+    #[test]
+    fn token_hashes_are_stable_sha256_digests() {
+        assert_eq!(token_hash("abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_ne!(token_hash("abc"), token_hash("abd"));
+    }
+
+    fn checkin_as_someone_else(request: &str) -> (u16, String) {
+        match request {
+            "GET /profiles/me" => (200, r#"{"id":8,"name":"Grace"}"#.to_string()),
+            "GET /hub_visits/8/2024-01-15" => (404, String::new()),
+            "PATCH /hub_visits/8/2024-01-15" => (200, r#"{"date":"2024-01-15","person":{"id":8,"name":"Grace"}}"#.to_string()),
+            _ => (401, String::new()),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_401_with_a_stale_cached_profile_is_retried_once_as_the_new_person() {
+        let (client, requests) = mock_api(checkin_as_someone_else).await;
+        let args = argv("tcurse -q checkin --date 2024-01-15");
+        let first = dispatch(Cli::parse_from(&args), &client, NameFormat::Full).await;
+        assert!(first.as_ref().is_err_and(unauthorized));
+
+        retry_with_fresh_profile(&args, &client, 7, NameFormat::Full).await.unwrap().unwrap();
+        assert_eq!(client.fetched_profile().map(|p| p.id), Some(8));
+        assert_eq!(
+            *requests.lock().unwrap(),
+            ["GET /hub_visits/7/2024-01-15", "GET /profiles/me", "GET /hub_visits/8/2024-01-15", "PATCH /hub_visits/8/2024-01-15"]
+        );
+    }
+
+    #[tokio::test]
+    async fn no_retry_when_the_profile_is_unchanged_or_cannot_be_fetched() {
+        let (client, requests) = mock_api(|_| (200, r#"{"id":7,"name":"Ada"}"#.to_string())).await;
+        assert!(retry_with_fresh_profile(&argv("tcurse whoami"), &client, 7, NameFormat::Full).await.is_none());
+        assert_eq!(*requests.lock().unwrap(), ["GET /profiles/me"]);
+
+        let (client, _) = mock_api(|_| (401, String::new())).await;
+        assert!(retry_with_fresh_profile(&argv("tcurse whoami"), &client, 7, NameFormat::Full).await.is_none());
+    }
+
+    fn ok_then_unauthorized(_: &str) -> (u16, String) {
+        static CALLS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        match CALLS.fetch_add(1, std::sync::atomic::Ordering::SeqCst) {
+            0 => (200, "[]".to_string()),
+            _ => (401, String::new()),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_401_after_a_successful_poll_reports_a_revoked_token() {
+        let (client, requests) = mock_api(ok_then_unauthorized).await;
+        assert!(client.get_visits("2024-01-15").await.is_ok());
+        let rejected = CliError::from(client.get_visits("2024-01-15").await.unwrap_err());
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        assert!(revoked_token(&rejected, false).is_none());
+        let revoked = revoked_token(&rejected, true).unwrap();
+        assert_eq!(revoked.to_string(), REVOKED_TOKEN);
+        assert_eq!(revoked.exit_code(), 2);
+        assert!(revoked_token(&CliError::Api(ApiError::Timeout), true).is_none());
+    }
+
+    #[test]
+    fn history_tags_match_case_insensitively_with_or_without_the_hash() {
+        let noted = |notes: Option<&str>| HubVisit { notes: notes.map(str::to_string), ..visit(7, "Ada") };
+        let visits = || vec![noted(Some("#Rust pairing")), noted(Some("#python")), noted(None), noted(Some("rust, untagged"))];
+        assert_eq!(tagged(visits(), None).len(), 4);
+        for tag in ["rust", "#RUST"] {
+            let kept = tagged(visits(), Some(tag));
+            assert_eq!(kept.len(), 1);
+            assert_eq!(kept[0].notes.as_deref(), Some("#Rust pairing"));
+        }
+        assert!(tagged(visits(), Some("go")).is_empty());
+    }
+
+    #[test]
+    fn first_and_last_keep_everything_when_fewer_entries_exist() {
+        assert_eq!(first_or_last(vec![1, 2, 3, 4], Some(2), None), [1, 2]);
+        assert_eq!(first_or_last(vec![1, 2, 3, 4], None, Some(2)), [3, 4]);
+        assert_eq!(first_or_last(vec![1, 2], Some(5), None), [1, 2]);
+        assert_eq!(first_or_last(vec![1, 2], None, Some(5)), [1, 2]);
+        assert!(first_or_last(Vec::<i32>::new(), None, Some(5)).is_empty());
+        assert_eq!(first_or_last(vec![1, 2], None, None), [1, 2]);
+    }
+
+    #[test]
+    fn first_and_last_cannot_be_combined() {
+        assert!(Cli::try_parse_from(["tcurse", "history", "7d", "today", "--first", "2", "--last", "2"]).is_err());
+        assert!(Cli::try_parse_from(["tcurse", "history", "7d", "today", "--last", "2"]).is_ok());
+    }
+
+    #[test]
+    fn timezones_are_validated_iana_names() {
+        assert_eq!(parse_timezone(" Europe/Berlin "), Ok(chrono_tz::Europe::Berlin));
+        assert_eq!(parse_config_value("timezone", "Asia/Tokyo"), Ok(toml::Value::String("Asia/Tokyo".to_string())));
+        assert!(parse_timezone("Mars/Olympus").unwrap_err().starts_with("Invalid timezone 'Mars/Olympus'"));
+        assert!(parse_config_value("timezone", "").is_err());
+    }
+
+    fn hub_at(utc: &str) -> DateTime<Tz> {
+        tcurse::time::hub_now_with(&tcurse::time::FixedClock(utc.parse().unwrap()))
+    }
+
+    #[test]
+    fn off_hours_notes_start_at_the_end_hour_and_stop_at_the_start_hour() {
+        let hours = (7, 22);
+        assert_eq!(off_hours_note(hub_at("2024-01-15T11:59:00Z"), hours).as_deref(), Some("Heads up: it's 06:59 in the hub"));
+        assert_eq!(off_hours_note(hub_at("2024-01-15T12:00:00Z"), hours), None);
+        assert_eq!(off_hours_note(hub_at("2024-01-16T02:59:00Z"), hours), None);
+        assert_eq!(off_hours_note(hub_at("2024-01-16T03:00:00Z"), hours).as_deref(), Some("Heads up: it's 22:00 in the hub"));
+    }
+
+    #[test]
+    fn off_hours_notes_name_the_weekend_day_at_any_hour() {
+        assert_eq!(off_hours_note(hub_at("2024-01-13T17:00:00Z"), (7, 22)).as_deref(), Some("Heads up: it's Saturday in the hub"));
+        assert_eq!(off_hours_note(hub_at("2024-01-14T17:00:00Z"), (7, 22)).as_deref(), Some("Heads up: it's Sunday in the hub"));
+        assert_eq!(off_hours_note(hub_at("2024-01-14T05:00:00Z"), (0, 24)).as_deref(), Some("Heads up: it's Sunday in the hub"));
+    }
+
+    #[test]
+    fn off_hours_notes_follow_custom_hub_hours() {
+        let hours = (9, 17);
+        assert_eq!(off_hours_note(hub_at("2024-01-15T13:59:00Z"), hours).as_deref(), Some("Heads up: it's 08:59 in the hub"));
+        assert_eq!(off_hours_note(hub_at("2024-01-15T14:00:00Z"), hours), None);
+        assert_eq!(off_hours_note(hub_at("2024-01-15T22:00:00Z"), hours).as_deref(), Some("Heads up: it's 17:00 in the hub"));
+        assert_eq!(off_hours_note(hub_at("2024-01-16T03:00:00Z"), (0, 24)), None);
+    }
+
+    fn checked_in_at(id: i64, created_at: Option<&str>) -> HubVisit {
+        HubVisit { created_at: created_at.map(str::to_string), ..visit(id, "Ada") }
+    }
+
+    fn ids(visits: &[HubVisit]) -> Vec<i64> {
+        visits.iter().map(|v| v.person.id).collect()
+    }
+
+    fn timed_roster() -> Vec<HubVisit> {
+        vec![
+            checked_in_at(1, Some("2024-01-15T13:30:00Z")),
+            checked_in_at(2, Some("2024-01-15T14:00:00Z")),
+            checked_in_at(3, Some("2024-01-15T16:59:00Z")),
+            checked_in_at(4, Some("2024-01-15T17:00:00Z")),
+            checked_in_at(5, Some("2024-01-15T22:15:00Z")),
+        ]
+    }
+
+    fn at(hhmm: &str) -> Option<NaiveTime> {
+        Some(parse_time(hhmm).unwrap())
+    }
+
+    #[test]
+    fn time_filters_apply_after_before_or_both_in_hub_time() {
+        let ny = chrono_tz::America::New_York;
+        assert_eq!(ids(&filter_by_time(timed_roster(), at("09:00"), None, ny).unwrap()), [2, 3, 4, 5]);
+        assert_eq!(ids(&filter_by_time(timed_roster(), None, at("12:00"), ny).unwrap()), [1, 2, 3]);
+        assert_eq!(ids(&filter_by_time(timed_roster(), at("09:00"), at("12:00"), ny).unwrap()), [2, 3]);
+        assert_eq!(ids(&filter_by_time(timed_roster(), None, None, ny).unwrap()), [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn the_before_bound_is_exclusive_and_the_after_bound_inclusive() {
+        let ny = chrono_tz::America::New_York;
+        assert_eq!(ids(&filter_by_time(timed_roster(), at("12:00"), at("17:15"), ny).unwrap()), [4]);
+        assert_eq!(ids(&filter_by_time(timed_roster(), at("11:59"), at("12:00"), ny).unwrap()), [3]);
+    }
+
+    #[test]
+    fn time_filters_convert_across_utc_midnight() {
+        let late = || vec![checked_in_at(1, Some("2024-01-16T02:30:00Z")), checked_in_at(2, Some("2024-01-15T23:30:00+00:00"))];
+        assert_eq!(ids(&filter_by_time(late(), at("21:00"), None, chrono_tz::America::New_York).unwrap()), [1]);
+        assert_eq!(ids(&filter_by_time(late(), None, at("03:00"), chrono_tz::UTC).unwrap()), [1]);
+        assert_eq!(ids(&filter_by_time(late(), None, at("09:00"), chrono_tz::Asia::Tokyo).unwrap()), [2]);
+    }
+
+    #[test]
+    fn time_filters_need_check_in_times() {
+        let roster = vec![checked_in_at(1, Some("2024-01-15T13:30:00Z")), checked_in_at(2, None)];
+        let err = filter_by_time(roster, at("09:00"), None, chrono_tz::America::New_York).unwrap_err();
+        assert!(err.contains("Check-in times are not available"), "{}", err);
+        assert_eq!(ids(&filter_by_time(vec![checked_in_at(2, None)], None, None, chrono_tz::UTC).unwrap()), [2]);
+    }
+
+    fn grouped(visits: &[HubVisit], bounds: (u32, u32)) -> Result<Vec<(&'static str, Vec<i64>)>, String> {
+        let groups = group_by_period(visits, bounds, chrono_tz::America::New_York)?;
+        Ok(groups.into_iter().map(|(name, group)| (name, group.iter().map(|v| v.person.id).collect())).collect())
+    }
+
+    #[test]
+    fn rosters_split_into_morning_afternoon_and_evening() {
+        let groups = grouped(&timed_roster(), (12, 17)).unwrap();
+        assert_eq!(groups, [("Morning", vec![1, 2, 3]), ("Afternoon", vec![4]), ("Evening", vec![5])]);
+        let counts: Vec<usize> = groups.iter().map(|(_, ids)| ids.len()).collect();
+        assert_eq!(counts, [3, 1, 1]);
+    }
+
+    #[test]
+    fn boundary_hours_start_the_later_period() {
+        let roster = [checked_in_at(1, Some("2024-01-15T16:59:59Z")), checked_in_at(2, Some("2024-01-15T17:00:00Z")), checked_in_at(3, Some("2024-01-15T22:00:00Z"))];
+        assert_eq!(grouped(&roster, (12, 17)).unwrap(), [("Morning", vec![1]), ("Afternoon", vec![2]), ("Evening", vec![3])]);
+        assert_eq!(grouped(&roster, (11, 12)).unwrap(), [("Morning", vec![]), ("Afternoon", vec![1]), ("Evening", vec![2, 3])]);
+    }
+
+    #[test]
+    fn periods_need_check_in_times() {
+        let err = grouped(&[checked_in_at(1, Some("2024-01-15T13:30:00Z")), checked_in_at(2, None)], (12, 17)).unwrap_err();
+        assert!(err.contains("--by-period can't be applied"), "{}", err);
+    }
+
+    #[test]
+    fn period_bounds_default_parse_and_reject_invalid_boundaries() {
+        assert_eq!(parse_period_bounds(None), Ok((12, 17)));
+        assert_eq!(parse_period_bounds(Some(" 11 , 18 ")), Ok((11, 18)));
+        for raw in ["17,12", "12,12", "12,25", "12", "noon,17", ""] {
+            let err = parse_period_bounds(Some(raw)).unwrap_err();
+            assert!(err.starts_with(&format!("Invalid TCURSE_PERIODS '{}'", raw)), "{}", err);
+        }
+    }
+
+    fn people(entries: &[(i64, &str)]) -> Vec<VisitPerson> {
+        entries.iter().map(|&(id, name)| VisitPerson { id, name: name.to_string() }).collect()
+    }
+
+    fn event_kinds(events: &[PresenceEvent]) -> Vec<(&str, i64)> {
+        events.iter().map(|e| (e.event, e.person_id)).collect()
+    }
+
+    #[test]
+    fn presence_events_report_arrivals_and_departures() {
+        let at = "2024-01-15T10:00:00-05:00";
+        let arrived = presence_events("2024-01-15", &people(&[(1, "Ada")]), &people(&[(1, "Ada"), (2, "Grace")]), at);
+        assert_eq!(event_kinds(&arrived), [("arrival", 2)]);
+        let departed = presence_events("2024-01-15", &people(&[(1, "Ada"), (2, "Grace")]), &people(&[(2, "Grace")]), at);
+        assert_eq!(event_kinds(&departed), [("departure", 1)]);
+    }
+
+    #[test]
+    fn unchanged_rosters_produce_no_events() {
+        let roster = people(&[(1, "Ada"), (2, "Grace")]);
+        assert!(presence_events("2024-01-15", &roster, &roster, "t").is_empty());
+        assert!(presence_events("2024-01-15", &[], &[], "t").is_empty());
+    }
+
+    #[test]
+    fn simultaneous_moves_list_arrivals_before_departures() {
+        let events = presence_events("2024-01-15", &people(&[(1, "Ada"), (2, "Grace")]), &people(&[(2, "Grace"), (3, "Alan")]), "t");
+        assert_eq!(event_kinds(&events), [("arrival", 3), ("departure", 1)]);
+    }
+
+    #[test]
+    fn presence_events_serialize_as_ndjson_objects() {
+        let events = presence_events("2024-01-15", &[], &people(&[(2, "Grace Hopper")]), "2024-01-15T10:00:00-05:00");
+        assert_eq!(
+            serde_json::to_string(&events[0]).unwrap(),
+            r#"{"event":"arrival","date":"2024-01-15","person_id":2,"name":"Grace Hopper","at":"2024-01-15T10:00:00-05:00"}"#
+        );
+    }
+
+    #[test]
+    fn summaries_aggregate_each_day() {
+        let date = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+        let days = vec![
+            (date("2024-01-15"), vec![visit(1, "Ada"), visit(7, "Me")]),
+            (date("2024-01-16"), vec![visit(1, "Ada"), visit(2, "Grace"), visit(3, "Alan")]),
+            (date("2024-01-17"), vec![]),
+            (date("2024-01-18"), vec![visit(2, "Grace"), visit(3, "Alan"), visit(7, "Me")]),
+        ];
+        let summary = summarize(&days, 7);
+        assert_eq!(
+            serde_json::to_value(&summary).unwrap(),
+            serde_json::json!({
+                "days": 4,
+                "check_ins": 8,
+                "unique_attendees": 4,
+                "busiest_day": "2024-01-16",
+                "busiest_day_count": 3,
+                "average_per_day": 2.0,
+                "your_days": 2,
+            })
+        );
+    }
+
+    #[test]
+    fn empty_ranges_summarize_to_zeros() {
+        let empty = serde_json::to_value(summarize(&[], 7)).unwrap();
+        assert_eq!(empty["days"], 0);
+        assert_eq!(empty["average_per_day"], 0.0);
+        assert_eq!(empty["busiest_day"], serde_json::Value::Null);
+
+        let quiet_day = [(NaiveDate::from_ymd_opt(2024, 1, 15).unwrap(), Vec::new())];
+        let quiet = serde_json::to_value(summarize(&quiet_day, 7)).unwrap();
+        assert_eq!((quiet["days"].clone(), quiet["busiest_day_count"].clone()), (1.into(), 0.into()));
+        assert_eq!(quiet["busiest_day"], serde_json::Value::Null);
     }
 }